@@ -1,104 +1,39 @@
-use crate::util::{RandomSignal, SinSignal, StatefulList, TabsState};
+use crate::bookmarks::Bookmarks;
+use crate::frecency::{self, Frecency};
+use crate::session::{DirSession, PaneSession, Session};
+use crate::config::{Config, DisplayMode, SortKey, View};
+use crate::util::event::{Event, Job};
+use crate::util::{StatefulList, TabsState};
+use crate::UserInterface::command::{self, Command, ExportFormat, PaletteEntry};
+use crate::UserInterface::disk::{DiskUsage, LargestChildren, Throughput};
+use crate::UserInterface::du::DuView;
+use crate::UserInterface::error::AppError;
+use crate::UserInterface::keymap::{Action, Keymap, Lookup};
+use crate::UserInterface::loader::{DirLoader, LoadMode};
+use crate::UserInterface::mouse::{ClickTracker, HitMap, Target, WHEEL_LINES};
+use crate::UserInterface::names;
+use crate::UserInterface::pane::{DirTab, Pane};
+use crate::UserInterface::prompt::{Prompt, PromptKind, PromptResult};
+use crate::UserInterface::status::{Message, Severity};
+use crate::UserInterface::theme::{EntryKind, Theme, ThemeSpec};
+use crate::UserInterface::{opener, platform};
+use crate::UserInterface::watch::DirWatcher;
 use std::fs::File;
-use std::io::Write;
+use std::io::{self, Write};
 use serde::{Deserialize, Serialize};
-use std::io::{self, BufRead, BufReader};
-use once_cell::sync::OnceCell;
-use main;
+use std::io::BufReader;
 use std::fmt::{self, Formatter, Display};
+use std::sync::mpsc;
 use std::fs;
 use std::path;
-use std::error::Error;
-use winrt;
-use windows::{
-    storage::StorageFile,
-    system::Launcher,
-};
-use regex::Regex;
-
-winrt::import!(
-    dependencies
-        os
-    types
-        windows::system::Launcher
-        windows::application_model::data_transfer::*
-);
-use windows::application_model::data_transfer::{DataPackage, Clipboard};
-
-
-const TASKS: [&str; 24] = [
-    "Item1", "Item2", "Item3", "Item4", "Item5", "Item6", "Item7", "Item8", "Item9", "Item10",
-    "Item11", "Item12", "Item13", "Item14", "Item15", "Item16", "Item17", "Item18", "Item19",
-    "Item20", "Item21", "Item22", "Item23", "Item24",
-];
-
-const CLIENTS: [&str; 24] = [
-    "Clients1", "Clients2", "Clients3", "Clients4", "Clients5", "Clients6", "Clients7", "Clients8", "Clients9", "Clients10",
-    "Clients11", "Clients12", "Clients13", "Clients14", "Clients15", "Clients16", "Clients17", "Clients18", "Clients19",
-    "Clients20", "Clients21", "Clients22", "Clients23", "Clients24",
-];
-
-const DATES: [&str; 24] = [
-    "21-07-1", "21-07-2", "21-07-3", "21-07-4", "21-07-5", "21-07-6", "21-07-7", "21-07-8", "21-07-9", "21-07-10",
-    "21-07-11", "21-07-12", "21-07-13", "21-07-14", "21-07-15", "21-07-16", "21-07-17", "21-07-18", "21-07-19",
-    "21-07-20", "21-07-21", "21-07-22", "21-07-23", "21-07-24",
-];
-
-const LOGS: [(&str, &str); 26] = [
-    ("Event1", "INFO"),
-    ("Event2", "INFO"),
-    ("Event3", "CRITICAL"),
-    ("Event4", "ERROR"),
-    ("Event5", "INFO"),
-    ("Event6", "INFO"),
-    ("Event7", "WARNING"),
-    ("Event8", "INFO"),
-    ("Event9", "INFO"),
-    ("Event10", "INFO"),
-    ("Event11", "CRITICAL"),
-    ("Event12", "INFO"),
-    ("Event13", "INFO"),
-    ("Event14", "INFO"),
-    ("Event15", "INFO"),
-    ("Event16", "INFO"),
-    ("Event17", "ERROR"),
-    ("Event18", "ERROR"),
-    ("Event19", "INFO"),
-    ("Event20", "INFO"),
-    ("Event21", "WARNING"),
-    ("Event22", "INFO"),
-    ("Event23", "INFO"),
-    ("Event24", "WARNING"),
-    ("Event25", "INFO"),
-    ("Event26", "INFO"),
-];
-
-const EVENTS: [(&str, u64); 24] = [
-    ("B1", 9),
-    ("B2", 12),
-    ("B3", 5),
-    ("B4", 8),
-    ("B5", 2),
-    ("B6", 4),
-    ("B7", 5),
-    ("B8", 9),
-    ("B9", 14),
-    ("B10", 15),
-    ("B11", 1),
-    ("B12", 0),
-    ("B13", 4),
-    ("B14", 6),
-    ("B15", 4),
-    ("B16", 6),
-    ("B17", 4),
-    ("B18", 7),
-    ("B19", 13),
-    ("B20", 8),
-    ("B21", 11),
-    ("B22", 9),
-    ("B23", 3),
-    ("B24", 5),
-];
+use std::time::{Duration, Instant, SystemTime};
+use crossterm::event::{KeyCode, KeyEvent, MouseButton, MouseEvent, MouseEventKind};
+
+
+/// 残しておくメッセージの数
+const LOG_LIMIT: usize = 500;
+/// ディスク容量を取り直す間隔
+const DISK_REFRESH: Duration = Duration::from_secs(1);
 
 #[derive(Serialize, Deserialize)]
 pub struct PathShortCut {
@@ -109,76 +44,263 @@ pub struct PathShortCut {
     five: String,
 }
 
-pub struct Signal<S: Iterator> {
-    source: S,
-    pub points: Vec<S::Item>,
-    tick_rate: usize,
+pub struct Server<'a> {
+    pub name: &'a str,
+    pub location: &'a str,
+    pub coords: (f64, f64),
+    pub status: &'a str,
+}
+
+#[derive(Clone)]
+pub struct Task  {
+    pub path: path::PathBuf,
+    pub is_dir: bool,
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+    pub hidden: bool,
+    pub is_symlink: bool,
+    pub executable: bool,
 }
 
-impl<S> Signal<S>
-where
-    S: Iterator,
-{
-    fn on_tick(&mut self) {
-        for _ in 0..self.tick_rate {
-            self.points.remove(0);
+impl Task {
+    pub fn new(path: path::PathBuf) -> Self {
+        let metadata = fs::metadata(&path).ok();
+        let is_symlink = fs::symlink_metadata(&path)
+            .map(|m| m.file_type().is_symlink())
+            .unwrap_or(false);
+        Task {
+            is_symlink,
+            ..Task::with_metadata(path, metadata.as_ref())
+        }
+    }
+
+    pub fn with_metadata(path: path::PathBuf, metadata: Option<&fs::Metadata>) -> Self {
+        let hidden = path
+            .file_name()
+            .map(|name| name.to_string_lossy().starts_with('.'))
+            .unwrap_or(false);
+        Task {
+            is_dir: metadata.map(|m| m.is_dir()).unwrap_or(false),
+            size: metadata.map(|m| m.len()).unwrap_or(0),
+            modified: metadata.and_then(|m| m.modified().ok()),
+            hidden: hidden || metadata.map(is_hidden_attribute).unwrap_or(false),
+            is_symlink: false,
+            executable: metadata.map(|m| is_executable(&path, m)).unwrap_or(false),
+            path,
+        }
+    }
+
+    /// ファイル名。ルートなど名前の無いパスはパス全体を使う。
+    pub fn name(&self) -> String {
+        match self.path.file_name() {
+            Some(name) => names::escape(name),
+            None => names::escape_path(&self.path),
+        }
+    }
+
+    /// エスケープしていない名前。書き出し用。
+    fn raw_name(&self) -> &path::Path {
+        self.path.file_name().map(path::Path::new).unwrap_or(&self.path)
+    }
+
+    /// 表示設定に従った名前。`Relative` は `root`（作業ディレクトリ）からの相対パス。
+    pub fn display_name(&self, mode: DisplayMode, root: &path::Path) -> String {
+        match mode {
+            DisplayMode::Name => self.name(),
+            DisplayMode::Relative => names::escape_path(&names::relative(&self.path, root)),
+            DisplayMode::Full => names::escape_path(&self.path),
+        }
+    }
+
+    /// 色分けに使う種類
+    pub fn kind(&self) -> EntryKind {
+        match (self.is_symlink, self.is_dir, self.executable) {
+            (true, _, _) => EntryKind::Symlink,
+            (_, true, _) => EntryKind::Directory,
+            (_, _, true) => EntryKind::Executable,
+            _ => EntryKind::File,
         }
-        self.points
-            .extend(self.source.by_ref().take(self.tick_rate));
     }
+
+    fn extension(&self) -> String {
+        self.path
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(windows)]
+fn is_hidden_attribute(metadata: &fs::Metadata) -> bool {
+    use std::os::windows::fs::MetadataExt;
+    const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+    metadata.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0
+}
+
+#[cfg(not(windows))]
+fn is_hidden_attribute(_metadata: &fs::Metadata) -> bool {
+    false
+}
+
+#[cfg(unix)]
+fn is_executable(_path: &path::Path, metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.is_file() && metadata.permissions().mode() & 0o111 != 0
 }
 
-pub struct Signals {
-    pub sin1: Signal<SinSignal>,
-    pub sin2: Signal<SinSignal>,
-    pub window: [f64; 2],
+#[cfg(not(unix))]
+fn is_executable(path: &path::Path, metadata: &fs::Metadata) -> bool {
+    const EXTENSIONS: [&str; 4] = ["exe", "bat", "cmd", "com"];
+    metadata.is_file()
+        && path
+            .extension()
+            .map(|e| EXTENSIONS.contains(&e.to_string_lossy().to_lowercase().as_str()))
+            .unwrap_or(false)
 }
 
-impl Signals {
-    fn on_tick(&mut self) {
-        self.sin1.on_tick();
-        self.sin2.on_tick();
-        self.window[0] += 1.0;
-        self.window[1] += 1.0;
+/// 設定の `[view]` に従って並べ替える
+pub fn sort_tasks(items: &mut [Task], view: &View) {
+    items.sort_by(|a, b| {
+        let order = match view.sort {
+            SortKey::Name => a.name().to_lowercase().cmp(&b.name().to_lowercase()),
+            SortKey::Size => a.size.cmp(&b.size),
+            SortKey::Mtime => a.modified.cmp(&b.modified),
+            SortKey::Extension => a
+                .extension()
+                .cmp(&b.extension())
+                .then_with(|| a.name().to_lowercase().cmp(&b.name().to_lowercase())),
+        };
+        let order = if view.reverse { order.reverse() } else { order };
+        if view.dirs_first {
+            b.is_dir.cmp(&a.is_dir).then(order)
+        } else {
+            order
+        }
+    });
+}
+
+/// カンマや引用符を含む値を CSV の 1 フィールドにする
+fn csv_field(value: &str) -> String {
+    match value.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", value.replace('"', "\"\"")),
+        false => value.to_string(),
     }
 }
 
-pub struct Server<'a> {
-    pub name: &'a str,
-    pub location: &'a str,
-    pub coords: (f64, f64),
-    pub status: &'a str,
+/// 書き出す 1 エントリ（JSON と CSV 用）。スクリプトで使えるよう、表示用のエスケープはしない。
+#[derive(Serialize)]
+struct ListingEntry {
+    name: String,
+    path: String,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    size: u64,
+    /// UNIX 時刻（秒）
+    modified: Option<u64>,
 }
 
-#[derive(Clone)]
-pub struct Task  {
-    pub folder_name: String,
+impl ListingEntry {
+    fn new(task: &Task) -> ListingEntry {
+        ListingEntry {
+            name: task.raw_name().to_string_lossy().into_owned(),
+            path: task.path.to_string_lossy().into_owned(),
+            kind: if task.is_dir { "dir" } else { "file" },
+            size: task.size,
+            modified: task
+                .modified
+                .and_then(|time| time.duration_since(SystemTime::UNIX_EPOCH).ok())
+                .map(|duration| duration.as_secs()),
+        }
+    }
 }
 
-impl Task {
-    pub fn new(folder_nmae: String) -> Self {
-        Task {
-            folder_name: folder_nmae.to_string().clone(),
+/// 一覧を指定の形式で書き出す
+pub fn write_listing(out: &mut dyn Write, items: &[Task], format: ExportFormat) -> io::Result<()> {
+    match format {
+        ExportFormat::Txt => {
+            for task in items.iter() {
+                names::write_path(out, task.raw_name())?;
+                out.write_all(b"\n")?;
+            }
+        }
+        ExportFormat::Csv => {
+            out.write_all(b"name,path,type,size,modified\n")?;
+            for entry in items.iter().map(ListingEntry::new) {
+                let row = [
+                    csv_field(&entry.name),
+                    csv_field(&entry.path),
+                    String::from(entry.kind),
+                    entry.size.to_string(),
+                    entry.modified.map(|secs| secs.to_string()).unwrap_or_default(),
+                ];
+                out.write_all(format!("{}\n", row.join(",")).as_bytes())?;
+            }
+        }
+        ExportFormat::Json => {
+            let entries: Vec<ListingEntry> = items.iter().map(ListingEntry::new).collect();
+            serde_json::to_writer_pretty(&mut *out, &entries)
+                .map_err(io::Error::from)?;
+            out.write_all(b"\n")?;
         }
     }
+    Ok(())
 }
 
-pub fn read_dir(path: &str) -> Result<Vec<path::PathBuf>, Box<dyn Error>> {
-    let dir = fs::read_dir(path)?;
-    let mut files: Vec<path::PathBuf> = Vec::new();
-    for item in dir.into_iter() {
-        files.push(item?.path());
+/// ディレクトリを読み切って、設定の `[view]` に従って並べた一覧を返す（端末を使わない `ls` 用）
+pub fn read_listing(dir: &path::Path, view: &View) -> io::Result<Vec<Task>> {
+    let mut tasks = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let task = Task::new(entry?.path());
+        if view.show_hidden || !task.hidden {
+            tasks.push(task);
+        }
     }
-    Ok(files)
+    sort_tasks(&mut tasks, view);
+    Ok(tasks)
 }
 
-pub fn launch_file(path: &str) -> winrt::Result<()> {
-    // ファイルパスから `StorageFile` オブジェクトを取得
-    let file = StorageFile::get_file_from_path_async(path).unwrap().get().unwrap();
+/// コマンドの引数のディレクトリ。省略すると `base`。
+fn directory_arg(
+    arg: Option<&str>,
+    base: &path::Path,
+    vars: &command::Vars,
+) -> Result<path::PathBuf, AppError> {
+    let dir = match arg {
+        Some(arg) => command::resolve_path(arg, base, vars),
+        None => base.to_path_buf(),
+    };
+    match dir.is_dir() {
+        true => Ok(dir),
+        false => Err(AppError::NotADirectory(dir)),
+    }
+}
 
-    // 既定のプログラムを使用して `file` を開く
-    Launcher::launch_file_async(file).unwrap().get().unwrap();
-    Ok(())
+/// 移動先のパスを Tab で補完する。候補が複数なら共通部分まで埋めて一覧を出し、
+/// 続けて押すと候補を順に入れる。
+fn complete_go_to(prompt: &mut Prompt, base: &path::Path, vars: &command::Vars) {
+    if !prompt.candidates.is_empty() {
+        if prompt.selection() == Some(prompt.input.as_str()) {
+            prompt.selected = (prompt.selected + 1) % prompt.candidates.len();
+        }
+        prompt.input = prompt.candidates[prompt.selected].clone();
+        return;
+    }
+    let completion = command::Completion {
+        start: 0,
+        candidates: command::complete_path(&prompt.input, base, vars),
+    };
+    if let Some(prefix) = completion.common_prefix() {
+        prompt.input = prefix;
+    }
+    prompt.selected = 0;
+    prompt.candidates = match completion.candidates.len() {
+        1 => Vec::new(),
+        _ => completion.candidates,
+    };
+}
+
+pub fn matches_filter(task: &Task, filter: &str) -> bool {
+    task.name().to_lowercase().contains(&filter.to_lowercase())
 }
 
 impl Display for Task {
@@ -190,80 +312,104 @@ impl Display for Task {
         // into a buffer (the first argument)
         // `write!`は`format!`に似ていますが、フォーマットされた文字列を
         // バッファ（第一引数）に書き込みます。
-        let current_dir = Regex::new(r"\\[^\\]*$").unwrap();
-        let mut current_dir = current_dir.find(&self.folder_name).unwrap().as_str().to_string();
-        current_dir.remove(0); // 先頭の\が邪魔なので消しておく
-        write!(f, "{}", current_dir)
+        write!(f, "{}", self.name())
     }
 }
 
 pub struct App<'a> {
     pub title: &'a str,
+    /// 相対パスの起点
+    pub root: path::PathBuf,
+    /// 直前の描画での部品の位置（マウス用）
+    pub hits: HitMap,
+    pub clicks: ClickTracker,
+    /// 別スレッドの処理がメインループに知らせる送り口。無ければ tick で拾う。
+    pub events: Option<mpsc::Sender<Event>>,
     pub should_quit: bool,
-    pub tabs: TabsState<'a>,
+    pub tabs: TabsState<&'a str>,
     pub show_chart: bool,
-    pub progress: f64,
-    pub sparkline: Signal<RandomSignal>,
-    pub folders: Vec<StatefulList<Task>>,
-    pub logs: StatefulList<(&'a str, &'a str)>,
-    pub signals: Signals,
-    pub barchart: Vec<(&'a str, u64)>,
+    pub panes: Vec<Pane>,
+    /// これまでのメッセージ（新しい順）
+    pub logs: StatefulList<Message>,
+    pub throughput: Throughput,
+    pub largest: Option<LargestChildren>,
+    /// 最後にディスク容量を取った時刻
+    pub disk_checked: Option<Instant>,
+    pub du: Option<DuView>,
+    /// パスに書いた `$NAME` などの引き方。既定は環境変数。
+    pub vars: Box<command::Vars>,
+    pub watcher: Option<DirWatcher>,
     pub servers: Vec<Server<'a>>,
     pub enhanced_graphics: bool,
+    /// フォーカス中のペイン
     pub folders_index: usize,
+    pub keymap: Keymap,
+    pub prompt: Option<Prompt>,
+    /// フォーカス中のペインのパンくずで選んでいる区切り
+    pub crumb: Option<usize>,
+    pub show_help: bool,
+    pub show_log: bool,
+    /// 終了時の書き出しに失敗した後なら、書き出さずに終了する
+    pub quit_without_export: bool,
+    /// 一時停止を求められた。端末を持つ呼び出し側が処理して戻す。
+    pub suspend_requested: bool,
+    /// `--pick`: 開く代わりに選んだパスを返して終了する
+    pub pick_mode: bool,
+    /// 選択モードで選ばれたパス。空なら取り消し。
+    pub picked: Vec<path::PathBuf>,
+    /// ステータスバーに表示中の操作結果
+    pub message: Option<Message>,
+    pub bookmarks: Bookmarks,
+    /// 訪れたディレクトリの記録
+    pub frecency: Frecency,
+    pub config: Config,
 }
 
 impl<'a> App<'a> {
-    pub fn new(title: &'a str, enhanced_graphics: bool) -> App<'a> {
-        let mut rand_signal = RandomSignal::new(0, 100);
-        let sparkline_points = rand_signal.by_ref().take(300).collect();
-        let mut sin_signal = SinSignal::new(0.2, 3.0, 18.0);
-        let sin1_points = sin_signal.by_ref().take(100).collect();
-        let mut sin_signal2 = SinSignal::new(0.1, 2.0, 10.0);
-        let sin2_points = sin_signal2.by_ref().take(200).collect();
-
-        let mut task_list_for1: Vec<Task> = Vec::new();
-        for folders in read_dir("E:\\SRC"){
-            for folder in folders {
-                task_list_for1.push(Task::new(folder.to_str().unwrap().to_string()));
-            }
-        }
+    /// 作業ディレクトリを起点にする
+    pub fn new(title: &'a str, enhanced_graphics: bool, config: Config, events: Option<mpsc::Sender<Event>>) -> App<'a> {
+        let root = std::env::current_dir().unwrap_or_else(|_| path::PathBuf::from("."));
+        App::with_root(title, enhanced_graphics, config, &root, events)
+    }
 
-        let mut task_list_for2: Vec<Task> = Vec::new();
-        for folders in read_dir("E:\\SRC"){
-            for folder in folders {
-                task_list_for2.push(Task::new(folder.to_str().unwrap().to_string()));
-            }
+    /// 設定に書かれた相対パス（開始ディレクトリ、ショートカット、書き出し先、ブックマーク）を
+    /// `root` から解決する。テストでは一時ディレクトリを渡す。
+    pub fn with_root(
+        title: &'a str,
+        enhanced_graphics: bool,
+        mut config: Config,
+        root: &path::Path,
+        events: Option<mpsc::Sender<Event>>,
+    ) -> App<'a> {
+        let general = &mut config.general;
+        for file in [&mut general.shortcuts, &mut general.export, &mut general.bookmarks] {
+            *file = root.join(&*file);
         }
-
-        App {
+        general.frecency = match general.frecency.take() {
+            Some(file) => Some(root.join(file)),
+            None => dirs::data_dir().map(|dir| dir.join("fexp").join("frecency.json")),
+        };
+        let mut app = App {
+            root: root.to_path_buf(),
+            hits: HitMap::default(),
+            clicks: ClickTracker::default(),
+            events,
             title,
             should_quit: false,
+            suspend_requested: false,
+            pick_mode: false,
+            picked: Vec::new(),
             tabs: TabsState::new(vec!["Tab0", "Tab1", "Tab2"]),
             show_chart: false,
-            progress: 0.0,
-            sparkline: Signal {
-                source: rand_signal,
-                points: sparkline_points,
-                tick_rate: 1,
-            },
-            folders: vec![StatefulList::with_items(task_list_for1),StatefulList::with_items(task_list_for2)] ,
+            panes: (0..config.view.panes).map(|_| Pane::new()).collect(),
             folders_index: 0,
-            logs: StatefulList::with_items(LOGS.to_vec()),
-            signals: Signals {
-                sin1: Signal {
-                    source: sin_signal,
-                    points: sin1_points,
-                    tick_rate: 5,
-                },
-                sin2: Signal {
-                    source: sin_signal2,
-                    points: sin2_points,
-                    tick_rate: 10,
-                },
-                window: [0.0, 20.0],
-            },
-            barchart: EVENTS.to_vec(),
+            logs: StatefulList::new(),
+            throughput: Throughput::new(200),
+            largest: None,
+            disk_checked: None,
+            du: None,
+            vars: Box::new(command::env_var),
+            watcher: DirWatcher::new(config.view.panes).ok(),
             servers: vec![
                 Server {
                     name: "NorthAmerica-1",
@@ -291,25 +437,209 @@ impl<'a> App<'a> {
                 },
             ],
             enhanced_graphics,
+            keymap: Keymap::new(&config.keys).unwrap_or_default(),
+            prompt: None,
+            crumb: None,
+            show_help: false,
+            show_log: false,
+            quit_without_export: false,
+            message: None,
+            bookmarks: Bookmarks::load(&config.general.bookmarks).unwrap_or_default(),
+            frecency: config
+                .general
+                .frecency
+                .as_deref()
+                .and_then(|file| Frecency::load(file).ok())
+                .unwrap_or_default(),
+            config,
+        };
+        for index in 0..app.panes.len() {
+            let dir = app.config.start_dir(index);
+            app.change_dir(index, &dir);
+        }
+        if app.watcher.is_none() {
+            app.notify(Severity::Warning, String::from("ディレクトリを監視できません。変更は自動で反映されません"));
+        }
+        app
+    }
+
+    /// ペインが表示しているディレクトリ。ドライブの一覧なら None。
+    pub fn pane_dir(&self, index: usize) -> Option<path::PathBuf> {
+        self.panes[index].current().dir()
+    }
+
+    /// フォーカス中のペインで選ばれているタブ
+    pub fn focused(&self) -> &DirTab {
+        self.panes[self.folders_index].current()
+    }
+
+    pub fn focused_mut(&mut self) -> &mut DirTab {
+        self.panes[self.folders_index].current_mut()
+    }
+
+    /// 各ペインのディスク容量を `DISK_REFRESH` ごとに取り直し、フォーカス中のペインが
+    /// 移動していれば直下のエントリのサイズ集計をやり直す
+    pub fn refresh_disk_info(&mut self) {
+        let due = match self.disk_checked {
+            Some(checked) => checked.elapsed() >= DISK_REFRESH,
+            None => true,
+        };
+        if due {
+            for pane in self.panes.iter_mut() {
+                pane.disk = pane.current().dir().and_then(|dir| DiskUsage::of(&dir).ok());
+            }
+            self.disk_checked = Some(Instant::now());
+        }
+
+        let dir = self.pane_dir(self.folders_index);
+        let stale = match (&self.largest, &dir) {
+            (Some(largest), Some(dir)) => &largest.dir != dir,
+            (None, Some(_)) => true,
+            (_, None) => false,
+        };
+        if stale {
+            if let Some(dir) = dir {
+                self.largest = Some(LargestChildren::start(dir, self.throughput.meter()));
+            }
+        }
+        if let Some(largest) = self.largest.as_mut() {
+            largest.poll();
+        }
+    }
+
+    /// フォーカス中のペインのディレクトリでディスク使用量ビューを開く／閉じる
+    pub fn toggle_du(&mut self) {
+        match self.du.take() {
+            Some(du) => {
+                // ビューで削除したエントリを一覧に反映する
+                self.reload_pane(self.folders_index, &du.root);
+            }
+            None => {
+                if let Some(dir) = self.pane_dir(self.folders_index) {
+                    self.du = Some(DuView::start(dir, self.throughput.meter(), self.events.clone()));
+                }
+            }
+        }
+    }
+
+    /// 使用量アナライザ表示中の操作
+    pub fn on_du_action(&mut self, action: Action) {
+        let du = match self.du.as_mut() {
+            Some(du) => du,
+            None => return,
+        };
+        match action {
+            Action::Down => du.next(),
+            Action::Up => du.previous(),
+            Action::Open | Action::FocusRight => du.enter(),
+            Action::Parent | Action::FocusLeft => du.leave(),
+            Action::Delete => du.request_delete(),
+            Action::Usage | Action::Quit | Action::ShowAll => self.toggle_du(),
+            Action::Help => self.show_help = !self.show_help,
+            _ => {}
+        }
+    }
+
+    /// ペインを別のディレクトリに移動する。検索による絞り込みは解除される。
+    /// 読込は別スレッドで行い、前の読込が終わっていなければ打ち切る。
+    pub fn change_dir(&mut self, index: usize, path: &path::Path) {
+        let dir = names::normalize(&self.root.join(path));
+        let pane = &mut self.panes[index];
+        pane.set_title(&dir);
+        *pane.current_mut() = DirTab {
+            loader: Some(DirLoader::start(dir.clone(), LoadMode::Stream, self.events.clone())),
+            dir: Some(dir),
+            ..DirTab::new()
+        };
+    }
+
+    /// ペインにドライブの一覧を出す（Windows で根より上へ移動したとき）
+    fn show_drives(&mut self, index: usize) {
+        let drives = platform::drives().into_iter().map(Task::new).collect();
+        let pane = &mut self.panes[index];
+        pane.tabs.titles[pane.tabs.index] = String::from("Drives");
+        *pane.current_mut() = DirTab {
+            list: StatefulList::with_items(drives),
+            ..DirTab::new()
+        };
+    }
+
+    /// ペインの一覧を読み直す。絞り込み中ならかけ直し、カーソルは位置ではなく名前で追従させる。
+    pub fn reload_pane(&mut self, index: usize, dir: &path::Path) {
+        let tab = self.panes[index].current_mut();
+        if tab.loader.is_some() {
+            // 読込中の結果がそのまま最新になる
+            return;
+        }
+        let mut loader = DirLoader::start(dir.to_path_buf(), LoadMode::Replace, self.events.clone());
+        loader.select = tab.selected().map(|task| task.path.clone());
+        loader.fallback = tab.list.state.selected();
+        tab.loader = Some(loader);
+    }
+
+    /// 読込中のタブに届いた分のエントリを反映する。裏のタブも読み進める。
+    pub fn poll_loaders(&mut self) {
+        let mut errors = Vec::new();
+        for tab in self.panes.iter_mut().flat_map(|pane| pane.dirs.iter_mut()) {
+            if let Some(text) = tab.poll(&self.config.view) {
+                errors.push(text);
+            }
+        }
+        for text in errors {
+            self.notify(Severity::Error, text);
+        }
+    }
+
+    /// ディレクトリを移動して読込中なら、読み込んだ件数
+    pub fn pane_loading(&self, index: usize) -> Option<usize> {
+        match &self.panes[index].current().loader {
+            Some(loader) if loader.mode == LoadMode::Stream => Some(loader.loaded),
+            _ => None,
         }
     }
 
-    pub fn next_dir(&self, path: &str) -> StatefulList<Task>{
-        let mut task_list: Vec<Task> = Vec::new();
-        for folders in read_dir(path){
-            for folder in folders {
-                task_list.push(Task::new(folder.to_str().unwrap().to_string()));
+    /// 監視対象を各ペインの表示中ディレクトリに合わせ、外部で変更されたペインを読み直す
+    pub fn sync_watches(&mut self) {
+        let dirs: Vec<Option<path::PathBuf>> =
+            (0..self.panes.len()).map(|index| self.pane_dir(index)).collect();
+        let watcher = match self.watcher.as_mut() {
+            Some(watcher) => watcher,
+            None => return,
+        };
+        let mut failures = Vec::new();
+        for (index, dir) in dirs.iter().enumerate() {
+            // ドライブの一覧は監視しない
+            if let Some(dir) = dir {
+                if let Err(e) = watcher.watch(index, dir) {
+                    failures.push(format!("{} を監視できません: {}", dir.display(), e));
+                }
             }
         }
-        StatefulList::with_items(task_list)
+        let changed = watcher.changed();
+        let reloads: Vec<(usize, path::PathBuf)> = (0..self.panes.len())
+            .filter_map(|index| watcher.dir(index).map(|dir| (index, dir.to_path_buf())))
+            .filter(|(_, dir)| changed.contains(dir))
+            .collect();
+        for (index, dir) in reloads {
+            self.reload_pane(index, &dir);
+        }
+        for text in failures {
+            self.notify(Severity::Warning, text);
+        }
     }
 
     pub fn on_up(&mut self) {
-        self.folders[self.folders_index].previous();
+        match self.du.as_mut() {
+            Some(du) => du.previous(),
+            None => self.focused_mut().list.previous(),
+        }
     }
 
     pub fn on_down(&mut self) {
-        self.folders[self.folders_index].next();
+        match self.du.as_mut() {
+            Some(du) => du.next(),
+            None => self.focused_mut().list.next(),
+        }
     }
 
     pub fn on_right(&mut self) {
@@ -321,179 +651,1218 @@ impl<'a> App<'a> {
     }
 
     pub fn on_enter_dir(&mut self) {
-        match self.folders[self.folders_index].state.selected() {
-            Some(x) => {
-                let path_target = &self.folders[self.folders_index].items[x].folder_name;
-                let path_target = path::Path::new(path_target);
-                let path_target = path::PathBuf::from(path_target);
-                match path_target.is_dir() {
-                    true => {
-                        self.folders[self.folders_index] = self.next_dir(path_target.to_str().unwrap());
-                    },
-                    false => {
-                        launch_file(path_target.to_str().unwrap());
-                    }
-                };
+        if let Some(du) = self.du.as_mut() {
+            du.enter();
+            return;
+        }
+        if self.pick_mode && !self.focused().marks.is_empty() {
+            self.picked = self.focused().marks.iter().cloned().collect();
+            self.should_quit = true;
+            return;
+        }
+        if let Some(task) = self.focused().selected() {
+            let path_target = task.path.clone();
+            match path_target.is_dir() {
+                false if self.pick_mode => {
+                    self.picked = vec![path_target];
+                    self.should_quit = true;
+                }
+                true => {
+                    self.change_dir(self.folders_index, &path_target);
+                    self.record_visit(&path_target);
+                },
+                false => {
+                    let result = self.open_file(&path_target);
+                    self.report(result);
+                }
+            };
+        }
+    }
+
+    /// パンくずを選んでいる間のキー。左右で区切りを選び、Enter で移動する。ほかのキーでやめる。
+    fn on_crumb_key(&mut self, event: KeyEvent, crumb: usize) {
+        let count = self
+            .pane_dir(self.folders_index)
+            .map(|dir| names::breadcrumb(&dir).len())
+            .unwrap_or(0);
+        self.crumb = match event.code {
+            KeyCode::Left | KeyCode::Char('h') => Some(crumb.saturating_sub(1)),
+            KeyCode::Right | KeyCode::Char('l') => Some((crumb + 1).min(count.saturating_sub(1))),
+            KeyCode::Enter => {
+                self.open_crumb(self.folders_index, crumb);
+                None
             }
-            _ => {}
+            _ => None,
+        };
+    }
+
+    /// ペインを今のディレクトリの `crumb` 番目の区切りへ移し、通ってきた子ディレクトリを選ぶ
+    pub fn open_crumb(&mut self, index: usize, crumb: usize) {
+        let dir = match self.pane_dir(index) {
+            Some(dir) => dir,
+            None => return,
+        };
+        let crumbs = names::breadcrumb(&dir);
+        let (ancestor, child) = match (crumbs.get(crumb), crumbs.get(crumb + 1)) {
+            (Some(ancestor), Some(child)) => (ancestor.path.clone(), child.path.clone()),
+            _ => return,
+        };
+        self.change_dir(index, &ancestor);
+        self.panes[index].current_mut().select_path(child);
+    }
+
+    /// ペインを親ディレクトリへ移し、通ってきたディレクトリを選ぶ。根の上は Windows ならドライブの一覧で、
+    /// それより上や `/` の上には移らない。
+    pub fn go_up(&mut self, index: usize) {
+        let dir = match self.pane_dir(index) {
+            Some(dir) => dir,
+            None => return,
+        };
+        match (dir.parent(), cfg!(windows)) {
+            (Some(parent), _) => self.change_dir(index, parent),
+            (None, true) => self.show_drives(index),
+            (None, false) => return,
         }
+        self.panes[index].current_mut().select_path(dir);
     }
 
     pub fn on_back_dir(&mut self) {
-        match self.folders[self.folders_index].state.selected() {
-            Some(x) => {
-                let path_target = &self.folders[self.folders_index].items[x].folder_name;
-                let path_target = path::Path::new(path_target);
-                let path_target = path::PathBuf::from(path_target);
-                let path_parent = path_target.parent().unwrap().parent();
-                match path_parent {
-                    Some(x) => {
-                        self.folders[self.folders_index] = self.next_dir(x.to_str().unwrap());
-                    },
-                    None => {},
-                }
-            },
-            _ => {}
-        }
+        self.go_up(self.folders_index);
     }
 
+    /// 絞り込みを解除して今の場所を読み直す。カーソルのあるエントリは変えない。
     pub fn on_all_disp(&mut self) {
-        match self.folders[self.folders_index].state.selected() {
-            Some(x) => {
-                let path_target = &self.folders[self.folders_index].items[x].folder_name;
-                let path_target = path::Path::new(path_target);
-                let path_target = path::PathBuf::from(path_target);
-                let path_parent = path_target.parent();
-                match path_parent {
-                    Some(x) => {
-                        self.folders[self.folders_index] = self.next_dir(x.to_str().unwrap());
-                    },
-                    None => {},
-                }
-            },
-            _ => {}
+        if self.du.is_some() {
+            self.toggle_du();
+            return;
+        }
+        let index = self.folders_index;
+        let selected = self.focused().selected().map(|task| task.path.clone());
+        match self.pane_dir(index) {
+            Some(dir) => self.change_dir(index, &dir),
+            None => self.show_drives(index),
+        }
+        if let Some(selected) = selected {
+            self.focused_mut().select_path(selected);
         }
     }
 
     pub fn on_focus_left_pain(&mut self) {
-        self.folders_index = 0;
+        self.folders_index = self.folders_index.saturating_sub(1);
     }
 
     pub fn on_focus_right_pain(&mut self) {
-        self.folders_index = 1;
+        self.folders_index = (self.folders_index + 1).min(self.panes.len() - 1);
+    }
+
+    /// フォーカス中のペインの右に新しいペインを開いてフォーカスする
+    pub fn split_pane(&mut self, dir: &path::Path) {
+        let index = self.folders_index + 1;
+        self.panes.insert(index, Pane::new());
+        if let Some(watcher) = self.watcher.as_mut() {
+            watcher.insert(index);
+        }
+        self.folders_index = index;
+        self.change_dir(index, dir);
+    }
+
+    /// フォーカス中のペインを閉じる。最後の 1 つは閉じない。
+    pub fn close_pane(&mut self) {
+        if self.panes.len() == 1 {
+            self.notify(Severity::Warning, String::from("最後のペインは閉じられません"));
+            return;
+        }
+        self.panes.remove(self.folders_index);
+        if let Some(watcher) = self.watcher.as_mut() {
+            watcher.remove(self.folders_index);
+        }
+        self.folders_index = self.folders_index.min(self.panes.len() - 1);
+    }
+
+    /// フォーカス中のペインに `dir` のタブを開く
+    pub fn open_dir_tab(&mut self, dir: &path::Path) {
+        self.panes[self.folders_index].open_tab();
+        self.change_dir(self.folders_index, dir);
+    }
+
+    pub fn close_dir_tab(&mut self) {
+        match self.panes[self.folders_index].close_tab() {
+            true => self.refresh_dir_tab(self.folders_index),
+            false => self.notify(Severity::Warning, String::from("最後のタブは閉じられません")),
+        }
     }
 
-    pub fn copy_path(&mut self) {
-        match self.folders[self.folders_index].state.selected() {
-            Some(x) => {
-                let path_target = &self.folders[self.folders_index].items[x].folder_name;
-                let path_target = path::Path::new(path_target);
-                let path_target = path_target.to_str().unwrap();
-                let content = DataPackage::new().unwrap();
-                content.set_text(path_target.to_string());
+    /// フォーカス中のペインで `index` 番目のタブに切り替える
+    pub fn select_dir_tab(&mut self, index: usize) {
+        let pane = &mut self.panes[self.folders_index];
+        if index < pane.dirs.len() && index != pane.tabs.index {
+            pane.tabs.index = index;
+            self.refresh_dir_tab(self.folders_index);
+        }
+    }
+
+    /// 裏にあった間は監視していないので、表に出たタブを読み直す
+    fn refresh_dir_tab(&mut self, index: usize) {
+        if let Some(dir) = self.pane_dir(index) {
+            self.reload_pane(index, &dir);
+        }
+    }
+
+    pub fn copy_path(&mut self) -> Result<(), AppError> {
+        if let Some(task) = self.focused().selected() {
+            let path_target = task.path.clone();
+            platform::copy_text(&path_target.to_string_lossy()).map_err(AppError::Clipboard)?;
+            self.notify(Severity::Info, format!("コピーしました: {}", names::escape_path(&path_target)));
+        }
+        Ok(())
+    }
+
+    pub fn open_file(&mut self, path: &path::Path) -> Result<(), AppError> {
+        opener::open(path, &self.config.openers).map_err(|e| AppError::Open(path.to_path_buf(), e))?;
+        self.notify(Severity::Info, format!("開きました: {}", path.display()));
+        Ok(())
+    }
+
+    /// ステータスバーに操作の結果を出し、履歴に残す
+    pub fn notify(&mut self, severity: Severity, text: String) {
+        let message = Message::new(severity, text);
+        self.logs.items.insert(0, message.clone());
+        self.logs.items.truncate(LOG_LIMIT);
+        self.message = Some(message);
+    }
 
-                Clipboard::set_content(content);
-                Clipboard::flush();
+    /// 失敗した操作をエラーとして表示する
+    pub fn report(&mut self, result: Result<(), AppError>) {
+        if let Err(e) = result {
+            self.notify(Severity::Error, e.to_string());
+        }
+    }
+
+    /// カーソルのあるエントリの印を切り替えて、次の行へ進む
+    pub fn toggle_mark(&mut self) {
+        let tab = self.focused_mut();
+        if let Some(path) = tab.selected().map(|task| task.path.clone()) {
+            if !tab.marks.remove(&path) {
+                tab.marks.insert(path);
             }
-            _ => {}
+            tab.list.next();
         }
     }
 
-    // TODO:ファイル読込処理
-    pub fn get_path_of_number(&mut self, number: usize) -> path::PathBuf {
-        let file = File::open("PathShortCut.json").expect("FileNotFoundError");
+    pub fn clear_marks(&mut self) {
+        let count = self.focused().marks.len();
+        self.focused_mut().marks.clear();
+        self.notify(Severity::Info, format!("{} 件の印を外しました", count));
+    }
+
+    /// フォーカス中のペインで印の付いたエントリの数と合計サイズ
+    pub fn marked_summary(&self) -> (usize, u64) {
+        let tab = self.focused();
+        tab.list
+            .items
+            .iter()
+            .filter(|task| tab.marks.contains(&task.path))
+            .fold((0, 0), |(count, size), task| (count + 1, size + task.size))
+    }
+
+    pub fn get_path_of_number(&mut self, number: usize) -> Result<path::PathBuf, AppError> {
+        let json = &self.config.general.shortcuts;
+        let file = File::open(json).map_err(|e| AppError::Io(json.clone(), e))?;
         let reader = BufReader::new(file);
 
-        let path_shortcut: PathShortCut = serde_json::from_reader(reader).expect("json's dataformat is invalid.");
+        let path_shortcut: PathShortCut =
+            serde_json::from_reader(reader).map_err(|e| AppError::Shortcut(json.clone(), e))?;
         let path_target = match number {
             1 => &path_shortcut.one,
             2 => &path_shortcut.two,
             3 => &path_shortcut.three,
             4 => &path_shortcut.four,
             5 => &path_shortcut.five,
-            _ => &path_shortcut.five,
+            _ => return Err(AppError::UnknownShortcut(number)),
         };
-        path::Path::new(path_target);
-        path::PathBuf::from(path_target)
+        let path_target = path::PathBuf::from(path_target);
+        match path_target.exists() {
+            true => Ok(path_target),
+            false => Err(AppError::MissingShortcut(number, path_target)),
+        }
     }
 
-    pub fn search_string_in_this_path(&mut self, search: &str) {
-        // TODO:filterがなぜか使えない...
-        let mut lst_new = Vec::new();
-        for i in self.folders[self.folders_index].items.iter() {
-            match i.folder_name.to_lowercase().contains(&search.to_lowercase()) {
-                true => {lst_new.push(i.clone());},
-                false => {}
-            }
+    fn go_to_shortcut(&mut self, number: usize) -> Result<(), AppError> {
+        let path_target = self.get_path_of_number(number)?;
+        match path_target.is_dir() {
+            true => self.change_dir(self.folders_index, &path_target),
+            false => self.open_file(&path_target)?,
         }
+        Ok(())
+    }
+
+    pub fn search_string_in_this_path(&mut self, search: &str) {
+        let lst_new: Vec<Task> = self.focused()
+            .list
+            .items
+            .iter()
+            .filter(|i| matches_filter(i, search))
+            .cloned()
+            .collect();
 
         match lst_new.is_empty() {
-            true => {},
+            true => {
+                self.notify(Severity::Warning, format!("`{}` に一致するものはありません", search));
+            },
             false => {
-                self.folders[self.folders_index] = StatefulList::with_items(lst_new);
+                self.notify(Severity::Info, format!("`{}` で {} 件に絞り込みました", search, lst_new.len()));
+                let tab = self.focused_mut();
+                tab.list = StatefulList::with_items(lst_new);
+                tab.filter = Some(search.to_string());
             }
         }
-        
+
     }
 
-    pub fn on_key(&mut self, c: char, pos: (u16, u16)) {
-        match c.is_numeric() {
-            true => {
-                let path_target = self.get_path_of_number(c as usize - 48);
-                match path_target.is_dir() {
-                    true => {
-                        self.folders[self.folders_index] = self.next_dir(path_target.to_str().unwrap());
-                    },
-                    false => {
-                        launch_file(path_target.to_str().unwrap());
-                    }
+    /// メインループに届いたイベントを処理する。端末そのものの操作（再描画、一時停止）は呼び出し側が行う。
+    pub fn on_event(&mut self, event: Event) {
+        match event {
+            Event::Key(key) => self.on_key_event(key),
+            Event::Mouse(mouse) => self.on_mouse(mouse),
+            // 配置が変わるので、次の描画までは古い当たり判定を使わない
+            Event::Resize => self.hits = HitMap::default(),
+            Event::Tick => self.on_tick(),
+            Event::Suspend => self.suspend_requested = true,
+            Event::Job(Job::Progress) => self.poll_loaders(),
+            Event::Job(Job::Message(severity, text)) => self.notify(severity, text),
+            // 操作できないので終了する
+            Event::InputClosed => self.should_quit = true,
+        }
+    }
+
+    /// マウスの操作を、指している部品に振り分ける。
+    /// クリックでペインにフォーカスして行を選び、ダブルクリックで開く。ホイールはフォーカスを変えずにスクロールする。
+    pub fn on_mouse(&mut self, event: MouseEvent) {
+        let click = matches!(event.kind, MouseEventKind::Down(MouseButton::Left));
+        if self.show_help || self.show_log {
+            if click {
+                self.show_help = false;
+                self.show_log = false;
+            }
+            return;
+        }
+        if self.prompt.is_some() {
+            return;
+        }
+        let target = self.hits.target(event.column, event.row);
+        match (event.kind, target) {
+            (MouseEventKind::Down(MouseButton::Left), Target::Tab(index)) => self.tabs.index = index,
+            (MouseEventKind::Down(MouseButton::Left), Target::PaneTab(index, tab)) => {
+                self.folders_index = index;
+                self.select_dir_tab(tab);
+            }
+            (MouseEventKind::Down(MouseButton::Left), Target::Crumb(index, crumb)) => {
+                self.folders_index = index;
+                self.crumb = None;
+                self.open_crumb(index, crumb);
+            }
+            (MouseEventKind::Down(MouseButton::Left), Target::Pane(index, row)) => {
+                self.folders_index = index;
+                let list = &mut self.panes[index].current_mut().list;
+                let item = match row.map(|row| list.offset + row) {
+                    Some(item) if item < list.items.len() => item,
+                    _ => return,
                 };
+                list.state.select(Some(item));
+                if self.clicks.click(index, item) {
+                    self.on_enter_dir();
+                }
+            }
+            (MouseEventKind::ScrollDown, Target::Pane(index, _)) => {
+                self.panes[index].current_mut().list.move_by(WHEEL_LINES as isize)
+            }
+            (MouseEventKind::ScrollUp, Target::Pane(index, _)) => {
+                self.panes[index].current_mut().list.move_by(-(WHEEL_LINES as isize))
             }
-            false =>{
-                match c {
-                    'e' => {
-                        self.should_quit = true;
-                        let mut file = File::create("task.txt").expect("writeError");
+            _ => {}
+        }
+    }
 
-                        for task in self.folders[self.folders_index].items.iter() {
-                            file.write(format!("{}\n",task).as_bytes()).unwrap();
+    /// 押されたキーを処理する。入力欄や確認中の問い合わせがあればそちらに渡し、
+    /// それ以外はキーマップで操作に変換する。
+    pub fn on_key_event(&mut self, event: KeyEvent) {
+        if self.show_help || self.show_log {
+            self.show_help = false;
+            self.show_log = false;
+            return;
+        }
+        if let Some(prompt) = self.prompt.as_mut() {
+            match prompt.on_key(event) {
+                PromptResult::Editing => self.update_palette(),
+                PromptResult::Complete => self.complete_command(),
+                PromptResult::Cancel => self.prompt = None,
+                PromptResult::Submit(input) => {
+                    let kind = prompt.kind;
+                    let selection = prompt.selection().and_then(Action::from_name);
+                    let selected = prompt.selected;
+                    self.prompt = None;
+                    match kind {
+                        PromptKind::Search => self.search_string_in_this_path(&input),
+                        PromptKind::Command => {
+                            let result = self.run_command(&input);
+                            self.report(result);
+                        }
+                        PromptKind::Palette => {
+                            if let Some(action) = selection {
+                                self.on_action(action);
+                            }
+                        }
+                        PromptKind::Jump => {
+                            let result = self.jump(&input, selected);
+                            self.report(result);
+                        }
+                        PromptKind::GoTo => {
+                            let result = self.go_to(&input);
+                            self.report(result);
+                        }
+                    }
+                }
+            }
+            return;
+        }
+        if let Some(crumb) = self.crumb {
+            self.on_crumb_key(event, crumb);
+            return;
+        }
+        if let Some(du) = self.du.as_mut() {
+            if du.pending_delete.is_some() {
+                match self.keymap.feed(event) {
+                    Lookup::Action(Action::Confirm) => {
+                        let name = du.pending_delete.map(|id| du.name_of(id));
+                        if let Err(e) = du.confirm_delete() {
+                            let name = name.unwrap_or_default();
+                            self.notify(Severity::Error, format!("{} を削除できません: {}", name, e));
                         }
                     }
-                    't' => {
-                        self.show_chart = !self.show_chart;
+                    Lookup::Pending => {}
+                    _ => du.cancel_delete(),
+                }
+                return;
+            }
+        }
+        if let Lookup::Action(action) = self.keymap.feed(event) {
+            self.on_action(action);
+        }
+    }
+
+    pub fn on_action(&mut self, action: Action) {
+        if self.du.is_some() && action != Action::Suspend {
+            self.on_du_action(action);
+            return;
+        }
+        match action {
+            // 選択モードでは何も選ばずに終了する
+            Action::Quit if self.pick_mode => self.should_quit = true,
+            Action::Quit => {
+                // 書き出しに失敗したら一度だけ止める。もう一度押せば書き出さずに終了する。
+                let export = self.config.general.export.clone();
+                match (self.export_pane(ExportFormat::Txt, &export), self.quit_without_export) {
+                    (Ok(()), _) | (Err(_), true) => self.should_quit = true,
+                    (Err(e), false) => {
+                        self.quit_without_export = true;
+                        self.notify(
+                            Severity::Error,
+                            format!("{} に書き出せません: {} (もう一度押すと書き出さずに終了)", export.display(), e),
+                        );
                     }
-                    'j' => { self.on_down(); }
-                    'k' => { self.on_up(); }
-                    'c' => { self.on_enter_dir(); }
-                    'l' => { self.on_focus_right_pain(); }
-                    'h' => { self.on_focus_left_pain(); }
-                    'q' => { self.on_back_dir(); }
-                    _ => {}
                 }
             }
+            Action::ToggleChart => { self.show_chart = !self.show_chart; }
+            Action::Usage => { self.toggle_du(); }
+            Action::Down => { self.on_down(); }
+            Action::Up => { self.on_up(); }
+            Action::PageDown => { for _ in 0..4 { self.on_down() } }
+            Action::PageUp => { for _ in 0..4 { self.on_up() } }
+            Action::Top => { self.focused_mut().list.first(); }
+            Action::Bottom => { self.focused_mut().list.last(); }
+            Action::Open => { self.on_enter_dir(); }
+            Action::Parent => { self.on_back_dir(); }
+            Action::ShowAll => { self.on_all_disp(); }
+            Action::FocusLeft => { self.on_focus_left_pain(); }
+            Action::FocusRight => { self.on_focus_right_pain(); }
+            Action::SplitPane => { self.split_pane(&self.current_dir()); }
+            Action::ClosePane => { self.close_pane(); }
+            Action::PrevTab => { self.on_left(); }
+            Action::NextTab => { self.on_right(); }
+            Action::NewDirTab => { self.open_dir_tab(&self.current_dir()); }
+            Action::CloseDirTab => { self.close_dir_tab(); }
+            Action::PrevDirTab => {
+                let pane = &self.panes[self.folders_index];
+                let count = pane.dirs.len();
+                self.select_dir_tab((pane.tabs.index + count - 1) % count);
+            }
+            Action::NextDirTab => {
+                let pane = &self.panes[self.folders_index];
+                self.select_dir_tab((pane.tabs.index + 1) % pane.dirs.len());
+            }
+            Action::Search => { self.prompt = Some(Prompt::new(PromptKind::Search)); }
+            Action::Command => { self.prompt = Some(Prompt::new(PromptKind::Command)); }
+            Action::Palette => {
+                self.prompt = Some(Prompt::new(PromptKind::Palette));
+                self.update_palette();
+            }
+            Action::Jump => {
+                self.prompt = Some(Prompt::new(PromptKind::Jump));
+                self.update_palette();
+            }
+            Action::GoTo => { self.prompt = Some(Prompt::new(PromptKind::GoTo)); }
+            Action::Breadcrumb => {
+                // 親ディレクトリの区切りから選び始める
+                let count = self.pane_dir(self.folders_index).map(|dir| names::breadcrumb(&dir).len());
+                self.crumb = count.map(|count| count.saturating_sub(2));
+            }
+            Action::CopyPath => {
+                let result = self.copy_path();
+                self.report(result);
+            }
+            Action::Mark => { self.toggle_mark(); }
+            Action::UnmarkAll => { self.clear_marks(); }
+            Action::Delete | Action::Confirm => {}
+            Action::Help => { self.show_help = !self.show_help; }
+            Action::Shortcut(number) => {
+                let result = self.go_to_shortcut(number as usize);
+                self.report(result);
+            }
+            Action::Log => { self.show_log = !self.show_log; }
+            Action::Suspend => match cfg!(unix) {
+                true => self.suspend_requested = true,
+                false => self.notify(Severity::Warning, "この環境では一時停止できません".to_string()),
+            },
+        }
+    }
+
+    /// `:` で入力したコマンドを実行する
+    pub fn run_command(&mut self, line: &str) -> Result<(), AppError> {
+        let base = self.current_dir();
+        match command::parse(line)? {
+            Command::Action(action) => self.on_action(action),
+            Command::Cd(dir) => {
+                let dir = directory_arg(Some(&dir), &base, &*self.vars)?;
+                self.change_dir(self.folders_index, &dir);
+            }
+            Command::Sort(key, reverse) => {
+                self.config.view.sort = key;
+                self.config.view.reverse = reverse;
+                for index in 0..self.panes.len() {
+                    self.sort_pane(index);
+                }
+                self.notify(Severity::Info, format!("{} 順に並べ替えました", format!("{:?}", key).to_lowercase()));
+            }
+            Command::Display(mode) => {
+                self.config.view.display = mode;
+                for tab in self.panes.iter_mut().flat_map(|pane| pane.dirs.iter_mut()) {
+                    tab.list.invalidate();
+                }
+                self.notify(Severity::Info, format!("{} で表示します", format!("{:?}", mode).to_lowercase()));
+            }
+            Command::Mkdir(name) => {
+                let dir = command::resolve_path(&name, &base, &*self.vars);
+                fs::create_dir_all(&dir).map_err(|e| AppError::Io(dir.clone(), e))?;
+                self.reload_pane(self.folders_index, &base);
+                self.notify(Severity::Info, format!("作成しました: {}", dir.display()));
+            }
+            Command::BookmarkAdd(name) => {
+                self.bookmarks.add(&name, &base);
+                self.save_bookmarks()?;
+                self.notify(Severity::Info, format!("ブックマーク `{}` に {} を保存しました", name, base.display()));
+            }
+            Command::BookmarkRm(name) => {
+                if self.bookmarks.remove(&name).is_none() {
+                    return Err(AppError::Command(format!("bookmark: `{}` はありません", name)));
+                }
+                self.save_bookmarks()?;
+                self.notify(Severity::Info, format!("ブックマーク `{}` を削除しました", name));
+            }
+            Command::BookmarkGo(name) => {
+                let dir = self
+                    .bookmarks
+                    .get(&name)
+                    .ok_or_else(|| format!("bookmark: `{}` はありません", name))?
+                    .to_path_buf();
+                self.change_dir(self.folders_index, &dir);
+                self.record_visit(&dir);
+            }
+            Command::Theme(name) => {
+                let spec = ThemeSpec {
+                    name: Some(name),
+                    ..self.config.theme_spec.clone()
+                };
+                self.config.theme = Theme::resolve(&spec, self.config.dir.as_deref())?;
+                self.notify(Severity::Info, format!("テーマ: {}", self.config.theme.name));
+                self.config.theme_spec = spec;
+            }
+            Command::Pane(dir) => {
+                let dir = directory_arg(dir.as_deref(), &base, &*self.vars)?;
+                self.split_pane(&dir);
+            }
+            Command::Tab(dir) => {
+                let dir = directory_arg(dir.as_deref(), &base, &*self.vars)?;
+                self.open_dir_tab(&dir);
+            }
+            Command::Jump(words) => self.jump(&words, 0)?,
+            Command::Export(format, file) => {
+                let file = command::resolve_path(&file.to_string_lossy(), &base, &*self.vars);
+                self.export_pane(format, &file).map_err(|e| AppError::Io(file.clone(), e))?;
+                let count = self.focused().list.items.len();
+                self.notify(Severity::Info, format!("{} 件を書き出しました: {}", count, file.display()));
+            }
+        }
+        Ok(())
+    }
+
+    /// フォーカス中のペインのディレクトリ。分からなければ作業ディレクトリ。
+    pub fn current_dir(&self) -> path::PathBuf {
+        self.pane_dir(self.folders_index)
+            .unwrap_or_else(|| self.root.clone())
+    }
+
+    /// 次の起動で戻せるように、ペインとタブのディレクトリ、カーソル、絞り込み、並び順を取り出す
+    pub fn session(&self) -> Session {
+        let panes = self
+            .panes
+            .iter()
+            .map(|pane| PaneSession {
+                tab: pane.tabs.index,
+                dirs: pane
+                    .dirs
+                    .iter()
+                    .filter_map(|tab| {
+                        Some(DirSession {
+                            dir: tab.dir()?,
+                            selected: tab.selected().map(|task| task.path.clone()),
+                            filter: tab.filter.clone(),
+                        })
+                    })
+                    .collect(),
+            })
+            .collect();
+        Session {
+            tab: self.tabs.index,
+            focus: self.folders_index,
+            sort: self.config.view.sort,
+            reverse: self.config.view.reverse,
+            panes,
+        }
+    }
+
+    /// 保存したセッションに戻す。無くなったディレクトリのタブは開かず、
+    /// 開けるタブが 1 つも無ければペインは今のままにする。
+    pub fn restore_session(&mut self, session: Session) {
+        self.config.view.sort = session.sort;
+        self.config.view.reverse = session.reverse;
+        self.tabs.index = session.tab.min(self.tabs.titles.len() - 1);
+        let panes: Vec<(usize, Vec<DirSession>)> = session
+            .panes
+            .into_iter()
+            .map(|pane| (pane.tab, pane.dirs.into_iter().filter(|saved| saved.dir.is_dir()).collect()))
+            .filter(|(_, dirs): &(usize, Vec<DirSession>)| !dirs.is_empty())
+            .collect();
+        if panes.is_empty() {
+            return;
+        }
+        // ペインの数が変わるので監視も作り直す
+        if self.watcher.is_some() {
+            self.watcher = DirWatcher::new(panes.len()).ok();
+        }
+        self.panes = panes.iter().map(|_| Pane::new()).collect();
+        for (index, (selected, dirs)) in panes.into_iter().enumerate() {
+            let count = dirs.len();
+            for (i, saved) in dirs.into_iter().enumerate() {
+                if i > 0 {
+                    self.panes[index].open_tab();
+                }
+                self.change_dir(index, &saved.dir);
+                let tab = self.panes[index].current_mut();
+                tab.filter = saved.filter;
+                if let Some(loader) = tab.loader.as_mut() {
+                    loader.select = saved.selected;
+                }
+            }
+            self.panes[index].tabs.index = selected.min(count - 1);
+        }
+        self.folders_index = session.focus.min(self.panes.len() - 1);
+    }
+
+    fn save_bookmarks(&self) -> Result<(), AppError> {
+        let file = &self.config.general.bookmarks;
+        self.bookmarks.save(file).map_err(|e| AppError::Io(file.clone(), e))
+    }
+
+    /// 訪れたディレクトリの記録を保存する。データディレクトリが無ければ何もしない。
+    pub fn save_frecency(&self) -> Result<(), AppError> {
+        match &self.config.general.frecency {
+            Some(file) => self.frecency.save(file).map_err(|e| AppError::Io(file.clone(), e)),
+            None => Ok(()),
+        }
+    }
+
+    fn record_visit(&mut self, dir: &path::Path) {
+        self.frecency.visit(&self.root.join(dir), frecency::now());
+    }
+
+    /// `words` に一致するディレクトリのうち、点数が `rank` 番目のものへフォーカス中のペインを移す
+    fn jump(&mut self, words: &str, rank: usize) -> Result<(), AppError> {
+        let dir = self
+            .frecency
+            .rank(words, frecency::now())
+            .into_iter()
+            .nth(rank)
+            .ok_or_else(|| format!("z: `{}` に一致するディレクトリはありません", words))?;
+        self.change_dir(self.folders_index, &dir);
+        self.record_visit(&dir);
+        Ok(())
+    }
+
+    /// 入力したパスへフォーカス中のペインを移す。ファイルならそのディレクトリを開いてファイルを選ぶ。
+    fn go_to(&mut self, input: &str) -> Result<(), AppError> {
+        let input = input.trim();
+        if input.is_empty() {
+            return Ok(());
+        }
+        let target = names::normalize(&command::resolve_path(input, &self.current_dir(), &*self.vars));
+        let metadata = fs::metadata(&target).map_err(|e| AppError::Io(target.clone(), e))?;
+        match (metadata.is_dir(), target.parent()) {
+            (true, _) => self.change_dir(self.folders_index, &target),
+            (false, Some(dir)) => {
+                self.change_dir(self.folders_index, dir);
+                self.focused_mut().select_path(target);
+            }
+            (false, None) => {}
+        }
+        Ok(())
+    }
+
+    /// 設定の並び順でペインを並べ直す。カーソルのあるエントリは変えない。
+    pub fn sort_pane(&mut self, index: usize) {
+        let list = &mut self.panes[index].current_mut().list;
+        let current = list
+            .state
+            .selected()
+            .and_then(|i| list.items.get(i))
+            .map(|task| task.path.clone());
+        sort_tasks(&mut list.items, &self.config.view);
+        list.invalidate();
+        if let Some(current) = current {
+            let position = list.items.iter().position(|task| task.path == current);
+            list.state.select(position);
+        }
+    }
 
+    /// フォーカス中のペインの一覧をファイルに書き出す
+    pub fn export_pane(&self, format: ExportFormat, file: &path::Path) -> io::Result<()> {
+        let mut file = File::create(file)?;
+        write_listing(&mut file, &self.focused().list.items, format)
+    }
 
+    /// Tab でコマンドラインを補完する。候補が 1 つなら確定し、複数なら共通部分まで埋めて一覧を出す。
+    fn complete_command(&mut self) {
+        let base = self.current_dir();
+        if let Some(prompt) = self.prompt.as_mut().filter(|prompt| prompt.kind == PromptKind::GoTo) {
+            complete_go_to(prompt, &base, &*self.vars);
+            return;
+        }
+        let names = self.bookmarks.names();
+        let themes = self.theme_names();
+        let prompt = match self.prompt.as_mut() {
+            Some(prompt) if prompt.kind == PromptKind::Command => prompt,
+            _ => return,
+        };
+        let completion = command::complete(&prompt.input, &base, &names, &themes, &*self.vars);
+        if let Some(prefix) = completion.common_prefix() {
+            prompt.input.truncate(completion.start);
+            prompt.input.push_str(&prefix);
+            if completion.candidates.len() == 1 && !prefix.ends_with(path::MAIN_SEPARATOR) {
+                prompt.input.push(' ');
+            }
         }
+        prompt.candidates = match completion.candidates.len() {
+            1 => Vec::new(),
+            _ => completion.candidates,
+        };
     }
 
-    pub fn add_task(&mut self) {
+    /// 組み込みのテーマと、設定ディレクトリの `themes/` にあるテーマファイルの名前
+    pub fn theme_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = Theme::bundled_names().iter().map(|n| n.to_string()).collect();
+        let files = self
+            .config
+            .dir
+            .as_ref()
+            .and_then(|dir| fs::read_dir(dir.join("themes")).ok());
+        for entry in files.into_iter().flatten().flatten() {
+            let path = entry.path();
+            if path.extension().map(|e| e == "toml").unwrap_or(false) {
+                if let Some(stem) = path.file_stem() {
+                    names.push(stem.to_string_lossy().into_owned());
+                }
+            }
+        }
+        names
+    }
 
+    /// パレットの候補を入力に合わせて絞り込む
+    fn update_palette(&mut self) {
+        let entries: Vec<PaletteEntry> = Action::all()
+            .iter()
+            .map(|action| PaletteEntry {
+                action: *action,
+                description: action.description(),
+            })
+            .collect();
+        let prompt = match self.prompt.as_mut() {
+            Some(prompt) => prompt,
+            None => return,
+        };
+        match prompt.kind {
+            PromptKind::Palette => {
+                prompt.candidates = command::palette(&prompt.input, entries)
+                    .into_iter()
+                    .map(|entry| entry.action.name())
+                    .collect();
+                prompt.selected = prompt.selected.min(prompt.candidates.len().saturating_sub(1));
+            }
+            PromptKind::Jump => {
+                prompt.candidates = self
+                    .frecency
+                    .rank(&prompt.input, frecency::now())
+                    .iter()
+                    .map(|dir| names::escape_path(dir))
+                    .collect();
+                prompt.selected = prompt.selected.min(prompt.candidates.len().saturating_sub(1));
+            }
+            // 入力が変わったら前の補完候補は消す
+            _ => prompt.candidates.clear(),
+        }
     }
 
     pub fn on_tick(&mut self) {
-        // Update progress
-        self.progress += 0.001;
-        if self.progress > 1.0 {
-            self.progress = 0.0;
+        if self.message.as_ref().map(Message::is_expired).unwrap_or(false) {
+            self.message = None;
+        }
+        self.throughput.on_tick();
+        self.poll_loaders();
+        self.sync_watches();
+        // ディスク情報が画面に出ているときだけ取り直す
+        if self.hits.disk_info.is_some() {
+            self.refresh_disk_info();
         }
 
-        self.sparkline.on_tick();
-        self.signals.on_tick();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::UserInterface::harness::Harness;
+    use crate::util::event::Event;
+    use crossterm::event::MouseEventKind;
+    use std::fs;
+
+    const FILES: [&str; 4] = ["sub/inner.txt", "a.txt", "bb.txt", ".hidden"];
+
+    #[test]
+    fn lists_start_dir_with_dirs_first() {
+        let harness = Harness::new(&FILES);
+        assert_eq!(harness.names(), vec!["sub", ".hidden", "a.txt", "bb.txt"]);
+        harness.assert_screen_contains("bb.txt");
+    }
+
+    #[test]
+    fn hides_dot_files_when_configured() {
+        let mut config = Config::default();
+        config.view.show_hidden = false;
+        let harness = Harness::with_config(&FILES, config);
+        assert_eq!(harness.names(), vec!["sub", "a.txt", "bb.txt"]);
+    }
+
+    #[test]
+    fn opens_a_directory_and_goes_back() {
+        let mut harness = Harness::new(&FILES);
+        harness.press("j enter");
+        assert_eq!(harness.names(), vec!["inner.txt"]);
+        assert_eq!(harness.app.current_dir(), harness.root().join("sub"));
+
+        harness.press("j q");
+        assert_eq!(harness.app.current_dir(), harness.root());
+        assert!(harness.names().contains(&String::from("sub")));
+    }
+
+    #[test]
+    fn moves_the_cursor_with_sequences() {
+        let mut harness = Harness::new(&FILES);
+        harness.press("G");
+        assert_eq!(harness.selected().as_deref(), Some("bb.txt"));
+        harness.press("g g");
+        assert_eq!(harness.selected().as_deref(), Some("sub"));
+        harness.press("j");
+        assert_eq!(harness.selected().as_deref(), Some(".hidden"));
+    }
+
+    #[test]
+    fn search_filters_the_pane() {
+        let mut harness = Harness::new(&FILES);
+        harness.press("/").type_text("bb").press("enter");
+        assert_eq!(harness.names(), vec!["bb.txt"]);
+        assert_eq!(harness.app.focused().filter.as_deref(), Some("bb"));
+    }
+
+    #[test]
+    fn sort_command_reorders_entries() {
+        let mut harness = Harness::new(&FILES);
+        // ファイルの中身は名前なので、名前が長いほど大きい
+        harness.press(":").type_text("sort size reverse").press("enter");
+        assert_eq!(harness.names(), vec!["sub", ".hidden", "bb.txt", "a.txt"]);
+    }
+
+    #[test]
+    fn mkdir_command_creates_and_lists_the_directory() {
+        let mut harness = Harness::new(&FILES);
+        harness.press(":").type_text("mkdir made").press("enter").settle();
+        assert!(harness.root().join("made").is_dir());
+        assert!(harness.names().contains(&String::from("made")));
+    }
+
+    #[test]
+    fn marks_are_summed_and_exported() {
+        let mut harness = Harness::new(&FILES);
+        harness.press("j j j space space");
+        assert_eq!(harness.app.marked_summary(), (2, ("a.txt".len() + "bb.txt".len()) as u64));
+        harness.assert_screen_contains("印 2");
+
+        harness.press(":").type_text("export csv out.csv").press("enter");
+        let csv = fs::read_to_string(harness.root().join("out.csv")).unwrap();
+        assert!(csv.starts_with("name,path,type,size,modified\n"));
+        assert!(csv.contains("\nsub,"));
+    }
+
+    #[test]
+    fn quit_exports_under_the_root() {
+        let mut harness = Harness::new(&FILES);
+        harness.press("e");
+        assert!(harness.app.should_quit);
+        let text = fs::read_to_string(harness.root().join("task.txt")).unwrap();
+        assert_eq!(text, "sub\n.hidden\na.txt\nbb.txt\n");
+    }
+
+    #[test]
+    fn unknown_command_is_reported() {
+        let mut harness = Harness::new(&FILES);
+        harness.press(":").type_text("nope").press("enter");
+        harness.assert_screen_contains("nope");
+        assert!(harness.app.logs.items.iter().any(|m| m.text.contains("nope")));
+    }
+
+    #[test]
+    fn click_selects_and_double_click_opens() {
+        let mut harness = Harness::new(&FILES);
+        let (column, row) = harness.find("bb.txt").expect("bb.txt on screen");
+        harness.click(column, row);
+        assert_eq!(harness.selected().as_deref(), Some("bb.txt"));
+
+        let (column, row) = harness.find("sub").expect("sub on screen");
+        harness.click(column, row).click(column, row);
+        assert_eq!(harness.app.current_dir(), harness.root().join("sub"));
+    }
+
+    #[test]
+    fn wheel_moves_the_selection_in_steps() {
+        let mut harness = Harness::new(&FILES);
+        let (column, row) = harness.find("sub").expect("sub on screen");
+        harness.mouse(MouseEventKind::ScrollDown, column, row);
+        assert_eq!(harness.selected().as_deref(), Some("bb.txt"));
+        harness.mouse(MouseEventKind::ScrollUp, column, row);
+        assert_eq!(harness.selected().as_deref(), Some("sub"));
+    }
+
+    #[test]
+    fn clicking_a_tab_switches_to_it() {
+        let mut harness = Harness::new(&FILES);
+        let title = harness.app.tabs.titles[1];
+        let (column, row) = harness.find(title).expect("tab on screen");
+        harness.click(column, row);
+        assert_eq!(harness.app.tabs.index, 1);
+    }
+
+    #[test]
+    fn narrow_terminals_stack_the_panes() {
+        let mut harness = Harness::new(&FILES);
+        let (wide_left, wide_right) = (harness.app.hits.panes[0].0, harness.app.hits.panes[1].0);
+        assert_eq!(wide_left.y, wide_right.y);
+
+        harness.resize(60, 30);
+        let (left, right) = (harness.app.hits.panes[0].0, harness.app.hits.panes[1].0);
+        assert_eq!(left.x, right.x);
+        assert!(right.y > left.y);
+        harness.assert_screen_contains("bb.txt");
+    }
+
+    #[test]
+    fn long_names_are_shortened_in_the_middle() {
+        let long = "a_rather_long_file_name_that_does_not_fit.txt";
+        let mut harness = Harness::new(&[long]);
+        harness.resize(40, 30);
+        harness.assert_screen_contains("…_does_not_fit.txt");
+        assert!(!harness.screen().contains(long));
+    }
+
+    #[test]
+    fn short_terminals_drop_the_tab_border() {
+        let mut harness = Harness::new(&FILES);
+        harness.resize(100, 10);
+        let first_line = harness.screen().lines().next().unwrap().to_string();
+        assert!(first_line.contains("Tab0"), "{}", first_line);
+        harness.assert_screen_contains("a.txt");
+    }
+
+    #[test]
+    fn panes_can_be_split_and_closed() {
+        let mut config = Config::default();
+        config.view.panes = 3;
+        let mut harness = Harness::with_config(&FILES, config);
+        assert_eq!(harness.app.hits.panes.len(), 3);
+
+        harness.press("l ctrl-w v");
+        assert_eq!((harness.app.panes.len(), harness.app.folders_index), (4, 2));
+        harness.press("j enter");
+        assert_eq!(harness.app.pane_dir(2), Some(harness.root().join("sub")));
+
+        harness.press("ctrl-w c");
+        assert_eq!((harness.app.panes.len(), harness.app.folders_index), (3, 2));
+        assert_eq!(harness.app.pane_dir(2), Some(harness.root()));
+        harness.press("ctrl-w c ctrl-w c ctrl-w c");
+        assert_eq!(harness.app.panes.len(), 1);
+        assert!(harness.app.logs.items[0].text.contains("最後のペイン"));
+    }
+
+    #[test]
+    fn each_pane_keeps_its_own_directory_tabs() {
+        let mut harness = Harness::new(&FILES);
+        harness.press(":").type_text("tab sub").press("enter").settle();
+        assert_eq!(harness.names(), vec!["inner.txt"]);
+        assert_eq!(harness.app.panes[0].tabs.titles.len(), 2);
+        harness.assert_screen_contains("│sub");
+
+        harness.press("g t");
+        assert_eq!(harness.app.current_dir(), harness.root());
+        assert_eq!(harness.app.panes[1].tabs.titles.len(), 1);
+
+        let (column, row) = harness.find("│sub").expect("tab title on screen");
+        harness.click(column + 1, row);
+        assert_eq!(harness.app.current_dir(), harness.root().join("sub"));
+        harness.press("ctrl-w x");
+        assert_eq!(harness.app.current_dir(), harness.root());
+        assert_eq!(harness.app.panes[0].tabs.titles.len(), 1);
+    }
+
+    #[test]
+    fn session_restores_tabs_cursor_filter_and_sort() {
+        let mut harness = Harness::new(&["sub/inner.txt", "sub/other.txt", "a.txt", "bb.txt"]);
+        harness.press("j j");
+        harness.press(":").type_text("tab sub").press("enter").settle();
+        harness.press("/").type_text("inner").press("enter");
+        harness.press(":").type_text("sort size reverse").press("enter");
+        harness.press("l j");
+        let session = harness.app.session();
+
+        harness.press("h ctrl-w x ctrl-w c");
+        harness.press(":").type_text("sort name").press("enter");
+        assert_eq!(harness.app.panes.len(), 1);
+
+        harness.app.restore_session(session);
+        harness.settle();
+        assert_eq!(harness.app.panes.len(), 2);
+        assert_eq!(harness.app.folders_index, 1);
+        assert_eq!(harness.selected().as_deref(), Some("sub"));
+        assert!(harness.app.config.view.reverse);
+
+        harness.press("h");
+        assert_eq!(harness.app.panes[0].tabs.titles.len(), 2);
+        assert_eq!(harness.names(), vec!["inner.txt"]);
+        assert_eq!(harness.app.focused().filter.as_deref(), Some("inner"));
+        harness.press("g t");
+        assert_eq!(harness.selected().as_deref(), Some("a.txt"));
+    }
+
+    #[test]
+    fn jump_prompt_ranks_visited_directories() {
+        let mut harness = Harness::new(&["sub/deep/x.txt", "subway/y.txt"]);
+        harness.press("j enter j enter");
+        harness.press(":").type_text("cd ../..").press("enter");
+        harness.press("j j enter");
+        assert_eq!(harness.app.frecency.entries.len(), 3);
+        harness.press(":").type_text("cd ..").press("enter");
+
+        harness.press("z").type_text("sub");
+        let candidates = harness.app.prompt.as_ref().map(|p| p.candidates.len());
+        assert_eq!(candidates, Some(2));
+        harness.assert_screen_contains("subway");
+        harness.press("down enter");
+        assert_eq!(harness.app.frecency.entries[&harness.root().join("subway")].count, 2);
+
+        harness.press(":").type_text("z deep").press("enter");
+        assert_eq!(harness.app.current_dir(), harness.root().join("sub/deep"));
+        harness.press(":").type_text("z nothing").press("enter");
+        assert!(harness.app.logs.items.iter().any(|m| m.text.contains("nothing")));
+    }
+
+    #[test]
+    fn go_to_prompt_completes_cycles_and_selects_files() {
+        let mut harness = Harness::new(&["sub/one/x.txt", "sub/other/y.txt", "a.txt"]);
+        harness.press("ctrl-l").type_text("sub/o").press("tab");
+        let input = |harness: &Harness| harness.app.prompt.as_ref().map(|p| p.input.clone());
+        assert_eq!(input(&harness).as_deref(), Some("sub/o"));
+        harness.assert_screen_contains("one/  other/");
+        harness.press("tab");
+        assert_eq!(input(&harness).as_deref(), Some("sub/one/"));
+        harness.press("tab tab");
+        assert_eq!(input(&harness).as_deref(), Some("sub/one/"));
+        harness.press("enter");
+        assert_eq!(harness.app.current_dir(), harness.root().join("sub/one"));
+
+        let root = harness.root().to_string_lossy().into_owned();
+        harness.app.vars = Box::new(move |name| match name {
+            "PROJECT" => Some(root.clone()),
+            _ => None,
+        });
+        harness.press("ctrl-l").type_text("$PROJECT/a.txt").press("enter");
+        assert_eq!(harness.app.current_dir(), harness.root());
+        assert_eq!(harness.selected().as_deref(), Some("a.txt"));
+
+        harness.press("ctrl-l").type_text("missing").press("enter");
+        assert!(harness.app.logs.items.iter().any(|m| m.text.contains("missing")));
+    }
+
+    #[test]
+    fn breadcrumb_segments_open_ancestors() {
+        let mut harness = Harness::new(&["sub/deep/x.txt"]);
+        harness.press("j enter j enter");
+        let separator = std::path::MAIN_SEPARATOR;
+        harness.assert_screen_contains(&format!("{}sub{}deep", separator, separator));
+
+        harness.press("b h l enter");
+        assert_eq!(harness.app.current_dir(), harness.root().join("sub"));
+        assert_eq!(harness.selected().as_deref(), Some("deep"));
+        assert_eq!(harness.app.crumb, None);
+
+        let root_name = harness.root().file_name().unwrap().to_string_lossy().into_owned();
+        let (column, row) = harness.find(&root_name).expect("breadcrumb on screen");
+        harness.click(column, row);
+        assert_eq!(harness.app.current_dir(), harness.root());
+        assert_eq!(harness.selected().as_deref(), Some("sub"));
+    }
+
+    #[test]
+    fn long_breadcrumbs_drop_the_root_side() {
+        let mut harness = Harness::new(&["aaaaaaaaaa/bbbbbbbbbb/cccccccccc/x.txt"]);
+        harness.press("j enter j enter j enter").resize(30, 20);
+        let separator = std::path::MAIN_SEPARATOR;
+        harness.assert_screen_contains(&format!("…{}bbbbbbbbbb{}cccccccccc", separator, separator));
+    }
+
+    #[test]
+    fn going_up_works_from_empty_directories_and_stops_at_the_root() {
+        let mut harness = Harness::new(&["a.txt", "empty/", "zzz/"]);
+        harness.press(":").type_text("cd empty").press("enter");
+        assert!(harness.names().is_empty());
+        harness.assert_screen_contains("empty");
+        harness.press("q");
+        assert_eq!(harness.app.current_dir(), harness.root());
+        assert_eq!(harness.selected().as_deref(), Some("empty"));
+
+        harness.press(":").type_text("cd zzz/../empty/.").press("enter");
+        assert_eq!(harness.app.current_dir(), harness.root().join("empty"));
+        harness.press("esc");
+        assert_eq!(harness.app.current_dir(), harness.root().join("empty"));
+
+        let top = harness.root().ancestors().last().unwrap().to_path_buf();
+        harness.app.change_dir(0, &top);
+        harness.settle().press("q");
+        assert_eq!(harness.app.current_dir(), top);
+    }
+
+    #[test]
+    fn session_skips_directories_that_are_gone() {
+        let mut harness = Harness::new(&FILES);
+        let mut session = harness.app.session();
+        session.panes[0].dirs[0].dir = harness.root().join("gone");
+        session.focus = 5;
+        harness.app.restore_session(session);
+        harness.settle();
+        assert_eq!(harness.app.panes.len(), 1);
+        assert_eq!(harness.app.folders_index, 0);
+        assert_eq!(harness.app.current_dir(), harness.root());
+    }
+
+    #[test]
+    fn disk_info_refreshes_only_while_shown() {
+        let mut harness = Harness::new(&FILES);
+        harness.app.on_event(Event::Tick);
+        assert!(harness.app.disk_checked.is_none());
+
+        harness.press("t");
+        harness.app.on_event(Event::Tick);
+        let checked = harness.app.disk_checked.expect("refreshed while shown");
+        assert!(harness.app.panes[0].disk.is_some());
+        // 間隔が空くまでは取り直さない
+        harness.app.on_event(Event::Tick);
+        assert_eq!(harness.app.disk_checked, Some(checked));
+
+        harness.resize(60, 30);
+        harness.app.disk_checked = None;
+        harness.app.on_event(Event::Tick);
+        assert!(harness.app.disk_checked.is_none());
+    }
+
+    #[test]
+    fn usage_view_deletes_in_the_background() {
+        let mut harness = Harness::new(&["big/nested/file.txt", "small.txt"]);
+        harness.press("u j");
+        let du = harness.app.du.as_ref().expect("usage view");
+        assert_eq!(du.selected.map(|id| du.name_of(id)).as_deref(), Some("big"));
+        harness.press("d");
+        assert!(harness.app.du.as_ref().unwrap().pending_delete.is_some());
+        // 確認以外のキーは取り消し
+        harness.press("esc");
+        assert!(harness.app.du.as_ref().unwrap().pending_delete.is_none());
+        assert!(harness.root().join("big").exists());
+        harness.press("d y");
+        assert!(!harness.root().join("big").exists());
+        assert!(harness.app.logs.items.iter().any(|m| m.text == "削除しました: big"));
+        harness.assert_screen_contains("small.txt");
+    }
+
+    #[test]
+    fn unreadable_directories_are_logged() {
+        let mut harness = Harness::new(&FILES);
+        let gone = harness.root().join("gone");
+        harness.app.change_dir(0, &gone);
+        harness.settle();
+        let expected = format!("{} を読めません", gone.display());
+        assert!(harness.app.logs.items.iter().any(|m| m.text.starts_with(&expected)));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn listings_write_real_paths() {
+        use super::{read_listing, write_listing, ExportFormat};
+        use crate::config::View;
+        let harness = Harness::new(&["back\\slash.txt"]);
+        let items = read_listing(&harness.root(), &View::default()).unwrap();
+        let path = harness.root().join("back\\slash.txt");
 
-        let log = self.logs.items.pop().unwrap();
-        self.logs.items.insert(0, log);
+        let mut json = Vec::new();
+        write_listing(&mut json, &items, ExportFormat::Json).unwrap();
+        let entries: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(entries[0]["name"], "back\\slash.txt");
+        assert_eq!(entries[0]["path"], path.to_str().unwrap());
 
-        let event = self.barchart.pop().unwrap();
-        self.barchart.insert(0, event);
+        let mut txt = Vec::new();
+        write_listing(&mut txt, &items, ExportFormat::Txt).unwrap();
+        assert_eq!(txt, b"back\\slash.txt\n");
     }
 }
@@ -0,0 +1,408 @@
+use crate::config::{DisplayMode, SortKey};
+use crate::UserInterface::keymap::Action;
+use std::cmp::Reverse;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 書き出し形式
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExportFormat {
+    Txt,
+    Csv,
+    Json,
+}
+
+impl ExportFormat {
+    pub fn from_name(name: &str) -> Option<ExportFormat> {
+        match name {
+            "txt" => Some(ExportFormat::Txt),
+            "csv" => Some(ExportFormat::Csv),
+            "json" => Some(ExportFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+/// `:` で入力するコマンド
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Action(Action),
+    Cd(String),
+    Sort(SortKey, bool),
+    Display(DisplayMode),
+    Mkdir(String),
+    BookmarkAdd(String),
+    BookmarkRm(String),
+    BookmarkGo(String),
+    Export(ExportFormat, PathBuf),
+    Theme(String),
+    /// ペインを分割して開く。省略すると今のディレクトリ。
+    Pane(Option<String>),
+    /// ペインにタブを開く。省略すると今のディレクトリ。
+    Tab(Option<String>),
+    /// 語に一致する、よく訪れるディレクトリへ移動する
+    Jump(String),
+}
+
+/// 操作名以外のコマンド
+const COMMANDS: [&str; 10] = ["cd", "sort", "display", "mkdir", "bookmark", "export", "theme", "pane", "tab", "z"];
+const BOOKMARK_COMMANDS: [&str; 3] = ["add", "rm", "go"];
+pub const EXPORT_FORMATS: [&str; 3] = ["csv", "json", "txt"];
+
+/// 補完できるコマンド名（操作名を含む）
+pub fn command_names() -> Vec<String> {
+    let mut names: Vec<String> = COMMANDS.iter().map(|c| c.to_string()).collect();
+    names.extend(Action::all().iter().map(|action| action.name()));
+    names.sort();
+    names
+}
+
+/// 最初の空白で分けたコマンド名と残り（残りの先頭の空白は除く）
+fn split_first(line: &str) -> (&str, &str) {
+    let line = line.trim_start();
+    match line.find(char::is_whitespace) {
+        Some(i) => (&line[..i], line[i..].trim_start()),
+        None => (line, ""),
+    }
+}
+
+pub fn parse(line: &str) -> Result<Command, String> {
+    let (name, rest) = split_first(line.trim_end());
+    let command = match name {
+        "" => return Err(String::from("コマンドが空です")),
+        "cd" => Command::Cd(required(rest, "cd <dir>")?),
+        "mkdir" => Command::Mkdir(required(rest, "mkdir <name>")?),
+        "theme" => Command::Theme(required(rest, "theme <name>")?),
+        "pane" => Command::Pane(optional(rest)),
+        "tab" => Command::Tab(optional(rest)),
+        "z" => Command::Jump(required(rest, "z <words>")?),
+        "sort" => {
+            let (key, order) = split_first(rest);
+            let key = SortKey::from_name(key).ok_or_else(|| {
+                format!("sort: {} のいずれかを指定してください", SortKey::NAMES.join(" | "))
+            })?;
+            let reverse = match order {
+                "" => false,
+                "reverse" => true,
+                other => return Err(format!("sort: `{}` は指定できません", other)),
+            };
+            Command::Sort(key, reverse)
+        }
+        "display" => Command::Display(DisplayMode::from_name(rest).ok_or_else(|| {
+            format!("display: {} のいずれかを指定してください", DisplayMode::NAMES.join(" | "))
+        })?),
+        "bookmark" => {
+            let (sub, name) = split_first(rest);
+            let name = required(name, "bookmark add|rm|go <name>")?;
+            match sub {
+                "add" => Command::BookmarkAdd(name),
+                "rm" => Command::BookmarkRm(name),
+                "go" => Command::BookmarkGo(name),
+                _ => return Err(String::from("bookmark add|rm|go <name>")),
+            }
+        }
+        "export" => {
+            let (format, file) = split_first(rest);
+            let format = ExportFormat::from_name(format)
+                .ok_or_else(|| String::from("export csv|json|txt <file>"))?;
+            Command::Export(format, PathBuf::from(required(file, "export csv|json|txt <file>")?))
+        }
+        name => match (Action::from_name(name), rest.is_empty()) {
+            (Some(action), true) => Command::Action(action),
+            (Some(_), false) => return Err(format!("{}: 引数は取りません", name)),
+            (None, _) => return Err(format!("不明なコマンド `{}`", name)),
+        },
+    };
+    Ok(command)
+}
+
+fn required(arg: &str, usage: &str) -> Result<String, String> {
+    match arg.is_empty() {
+        true => Err(format!("使い方: {}", usage)),
+        false => Ok(arg.to_string()),
+    }
+}
+
+fn optional(arg: &str) -> Option<String> {
+    match arg.is_empty() {
+        true => None,
+        false => Some(arg.to_string()),
+    }
+}
+
+/// 変数と先頭の `~` を展開し、相対パスを `base` からのパスにする
+pub fn resolve_path(arg: &str, base: &Path, vars: &Vars) -> PathBuf {
+    let arg = expand_vars(arg, vars);
+    let expanded = match (arg.strip_prefix('~'), dirs::home_dir()) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with(['/', '\\']) => {
+            home.join(rest.trim_start_matches(['/', '\\']))
+        }
+        _ => PathBuf::from(arg),
+    };
+    base.join(expanded)
+}
+
+/// 変数名から値を引く関数。パスの展開に使う。
+pub type Vars = dyn Fn(&str) -> Option<String>;
+
+/// 環境変数を引く。`vars` を受け取る関数に普段渡すもの。
+pub fn env_var(name: &str) -> Option<String> {
+    std::env::var(name).ok()
+}
+
+/// `$NAME`、`${NAME}`、`%NAME%` を `vars` で引いた値に置き換える。定義されていない変数はそのまま残す。
+pub fn expand_vars(arg: &str, vars: &Vars) -> String {
+    let mut expanded = String::new();
+    let mut rest = arg;
+    while let Some(i) = rest.find(['$', '%']) {
+        expanded.push_str(&rest[..i]);
+        let sigil = &rest[i..i + 1];
+        let after = &rest[i + 1..];
+        let (name, len) = match sigil {
+            "$" if after.starts_with('{') => match after.find('}') {
+                Some(end) => (&after[1..end], end + 1),
+                None => ("", 0),
+            },
+            "$" => {
+                let end = after
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(after.len());
+                (&after[..end], end)
+            }
+            _ => match after.find('%') {
+                Some(end) => (&after[..end], end + 1),
+                None => ("", 0),
+            },
+        };
+        match vars(name) {
+            Some(value) if !name.is_empty() => {
+                expanded.push_str(&value);
+                rest = &after[len..];
+            }
+            _ => {
+                expanded.push_str(sigil);
+                rest = after;
+            }
+        }
+    }
+    expanded.push_str(rest);
+    expanded
+}
+
+/// 補完候補。`start` は入力のうち置き換える部分の開始位置（バイト）。
+pub struct Completion {
+    pub start: usize,
+    pub candidates: Vec<String>,
+}
+
+impl Completion {
+    /// 候補に共通する先頭部分
+    pub fn common_prefix(&self) -> Option<String> {
+        let first = self.candidates.first()?;
+        let mut prefix = first.clone();
+        for candidate in &self.candidates[1..] {
+            let len = prefix
+                .char_indices()
+                .zip(candidate.chars())
+                .find(|((_, a), b)| a != b)
+                .map(|((i, _), _)| i)
+                .unwrap_or_else(|| prefix.len().min(candidate.len()));
+            prefix.truncate(len);
+        }
+        Some(prefix)
+    }
+}
+
+/// 入力途中のコマンドラインを補完する。パスは `base` から、ブックマーク名とテーマ名は
+/// 渡された一覧から探す。
+pub fn complete(
+    line: &str,
+    base: &Path,
+    bookmarks: &[String],
+    themes: &[String],
+    vars: &Vars,
+) -> Completion {
+    let (name, rest) = split_first(line);
+    let arg_start = line.len() - line.trim_start().len() + name.len();
+    if rest.is_empty() && !line[arg_start..].starts_with(char::is_whitespace) {
+        return Completion {
+            start: arg_start - name.len(),
+            candidates: matching(name, command_names()),
+        };
+    }
+    let rest_start = line.len() - rest.len();
+    match name {
+        "cd" | "mkdir" | "pane" | "tab" => Completion {
+            start: rest_start,
+            candidates: complete_path(rest, base, vars),
+        },
+        "theme" => Completion {
+            start: rest_start,
+            candidates: matching(rest, themes.to_vec()),
+        },
+        "sort" => Completion {
+            start: rest_start,
+            candidates: matching(rest, SortKey::NAMES.iter().map(|s| s.to_string()).collect()),
+        },
+        "display" => Completion {
+            start: rest_start,
+            candidates: matching(rest, DisplayMode::NAMES.iter().map(|s| s.to_string()).collect()),
+        },
+        "bookmark" | "export" => {
+            let (sub, arg) = split_first(rest);
+            let words: Vec<String> = match name {
+                "bookmark" => BOOKMARK_COMMANDS.iter().map(|s| s.to_string()).collect(),
+                _ => EXPORT_FORMATS.iter().map(|s| s.to_string()).collect(),
+            };
+            if arg.is_empty() && !rest[sub.len()..].starts_with(char::is_whitespace) {
+                return Completion {
+                    start: rest_start,
+                    candidates: matching(sub, words),
+                };
+            }
+            let arg_start = line.len() - arg.len();
+            let candidates = match (name, sub) {
+                ("bookmark", "rm") | ("bookmark", "go") => matching(arg, bookmarks.to_vec()),
+                ("export", _) => complete_path(arg, base, vars),
+                _ => Vec::new(),
+            };
+            Completion {
+                start: arg_start,
+                candidates,
+            }
+        }
+        _ => Completion {
+            start: line.len(),
+            candidates: Vec::new(),
+        },
+    }
+}
+
+fn matching(prefix: &str, words: Vec<String>) -> Vec<String> {
+    words.into_iter().filter(|word| word.starts_with(prefix)).collect()
+}
+
+/// 入力したパスの最後の要素を補完する。ディレクトリには区切り文字を付ける。
+pub fn complete_path(arg: &str, base: &Path, vars: &Vars) -> Vec<String> {
+    let split = arg.rfind(['/', '\\']).map(|i| i + 1).unwrap_or(0);
+    let (dir_part, prefix) = arg.split_at(split);
+    let dir = match dir_part.is_empty() {
+        true => base.to_path_buf(),
+        false => resolve_path(dir_part, base, vars),
+    };
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    let mut candidates: Vec<String> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if !name.starts_with(prefix) || (prefix.is_empty() && name.starts_with('.')) {
+                return None;
+            }
+            let is_dir = entry.path().is_dir();
+            Some(match is_dir {
+                true => format!("{}{}{}", dir_part, name, std::path::MAIN_SEPARATOR),
+                false => format!("{}{}", dir_part, name),
+            })
+        })
+        .collect();
+    candidates.sort();
+    candidates
+}
+
+/// `pattern` の文字が順に `text` に現れれば一致とし、連続や単語の先頭での一致を高く評価する
+pub fn fuzzy_score(pattern: &str, text: &str) -> Option<i32> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut position = 0;
+    let mut previous: Option<usize> = None;
+    for c in pattern.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let found = (position..text.len()).find(|&i| text[i] == c)?;
+        score += 1;
+        if previous.map(|p| p + 1 == found).unwrap_or(false) {
+            score += 5;
+        }
+        if found == 0 || !text[found - 1].is_alphanumeric() {
+            score += 3;
+        }
+        score -= (found - position) as i32;
+        previous = Some(found);
+        position = found + 1;
+    }
+    Some(score)
+}
+
+/// コマンドパレットの 1 行
+pub struct PaletteEntry {
+    pub action: Action,
+    pub description: String,
+}
+
+/// 操作名と説明を `pattern` であいまい検索し、よく一致する順に並べる
+pub fn palette(pattern: &str, entries: Vec<PaletteEntry>) -> Vec<PaletteEntry> {
+    let mut scored: Vec<(i32, PaletteEntry)> = entries
+        .into_iter()
+        .filter_map(|entry| {
+            let name = fuzzy_score(pattern, &entry.action.name());
+            let description = fuzzy_score(pattern, &entry.description).map(|score| score - 10);
+            name.max(description).map(|score| (score, entry))
+        })
+        .collect();
+    scored.sort_by_key(|entry| Reverse(entry.0));
+    scored.into_iter().map(|(_, entry)| entry).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_commands() {
+        assert_eq!(parse("sort size reverse"), Ok(Command::Sort(SortKey::Size, true)));
+        assert_eq!(parse("  cd  ~/src  "), Ok(Command::Cd(String::from("~/src"))));
+        assert_eq!(parse("display full"), Ok(Command::Display(DisplayMode::Full)));
+        assert_eq!(parse("tab"), Ok(Command::Tab(None)));
+        assert_eq!(parse("pane src"), Ok(Command::Pane(Some(String::from("src")))));
+        assert_eq!(parse("z fo ba"), Ok(Command::Jump(String::from("fo ba"))));
+        assert_eq!(parse("toggle_chart"), Ok(Command::Action(Action::ToggleChart)));
+        assert!(parse("toggle_chart now").is_err());
+        assert!(parse("sort colour").is_err());
+        assert!(parse("").is_err());
+    }
+
+    #[test]
+    fn completes_names_and_arguments() {
+        let base = Path::new(".");
+        let completion = complete("so", base, &[], &[], &env_var);
+        assert_eq!(completion.candidates, vec!["sort"]);
+        let completion = complete("sort m", base, &[], &[], &env_var);
+        assert_eq!((completion.start, completion.candidates), (5, vec![String::from("mtime")]));
+        let bookmarks = vec![String::from("work"), String::from("home")];
+        let completion = complete("bookmark go w", base, &bookmarks, &[], &env_var);
+        assert_eq!(completion.candidates, vec!["work"]);
+    }
+
+    #[test]
+    fn common_prefix_of_candidates() {
+        let completion = Completion {
+            start: 0,
+            candidates: vec![String::from("bookmark"), String::from("bottom")],
+        };
+        assert_eq!(completion.common_prefix().as_deref(), Some("bo"));
+    }
+
+    #[test]
+    fn expands_environment_variables() {
+        let vars = |name: &str| match name {
+            "DATA" => Some(String::from("/srv/data")),
+            _ => None,
+        };
+        assert_eq!(expand_vars("$DATA/logs", &vars), "/srv/data/logs");
+        assert_eq!(expand_vars("${DATA}x", &vars), "/srv/datax");
+        assert_eq!(expand_vars("%DATA%\\logs", &vars), "/srv/data\\logs");
+        assert_eq!(expand_vars("$UNSET/100%", &vars), "$UNSET/100%");
+        assert_eq!(resolve_path("$DATA", Path::new("/base"), &vars), PathBuf::from("/srv/data"));
+    }
+}
@@ -0,0 +1,178 @@
+use std::cmp::Reverse;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+
+/// ペインが置かれているファイルシステムの容量
+#[derive(Clone, Copy, Debug)]
+pub struct DiskUsage {
+    pub total: u64,
+    pub available: u64,
+}
+
+impl DiskUsage {
+    pub fn of(path: &Path) -> io::Result<DiskUsage> {
+        Ok(DiskUsage {
+            total: fs2::total_space(path)?,
+            available: fs2::available_space(path)?,
+        })
+    }
+
+    pub fn used(&self) -> u64 {
+        self.total.saturating_sub(self.available)
+    }
+
+    pub fn ratio(&self) -> f64 {
+        match self.total {
+            0 => 0.0,
+            total => self.used() as f64 / total as f64,
+        }
+    }
+}
+
+/// バックグラウンドジョブが処理したバイト数を数えるカウンタ。
+/// ワーカースレッドに clone して渡し、`Throughput` が tick ごとに回収する。
+#[derive(Clone, Default)]
+pub struct JobMeter(Arc<AtomicU64>);
+
+impl JobMeter {
+    pub fn add(&self, bytes: u64) {
+        self.0.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    fn take(&self) -> u64 {
+        self.0.swap(0, Ordering::Relaxed)
+    }
+}
+
+/// tick ごとのジョブ処理量の履歴（スパークライン用）
+pub struct Throughput {
+    meter: JobMeter,
+    pub points: Vec<u64>,
+    width: usize,
+}
+
+impl Throughput {
+    pub fn new(width: usize) -> Throughput {
+        Throughput {
+            meter: JobMeter::default(),
+            points: vec![0; width],
+            width,
+        }
+    }
+
+    pub fn meter(&self) -> JobMeter {
+        self.meter.clone()
+    }
+
+    pub fn on_tick(&mut self) {
+        self.points.push(self.meter.take());
+        while self.points.len() > self.width {
+            self.points.remove(0);
+        }
+    }
+
+    pub fn last(&self) -> u64 {
+        self.points.last().copied().unwrap_or(0)
+    }
+}
+
+/// ディレクトリ直下の各エントリの合計サイズを別スレッドで集計する
+pub struct LargestChildren {
+    pub dir: PathBuf,
+    pub items: Vec<(String, u64)>,
+    rx: mpsc::Receiver<(String, u64)>,
+    cancel: Arc<AtomicBool>,
+}
+
+impl LargestChildren {
+    pub fn start(dir: PathBuf, meter: JobMeter) -> LargestChildren {
+        let (tx, rx) = mpsc::channel();
+        let cancel = Arc::new(AtomicBool::new(false));
+        {
+            let dir = dir.clone();
+            let cancel = cancel.clone();
+            thread::spawn(move || {
+                let entries = match fs::read_dir(&dir) {
+                    Ok(entries) => entries,
+                    Err(_) => return,
+                };
+                for entry in entries.flatten() {
+                    if cancel.load(Ordering::Relaxed) {
+                        return;
+                    }
+                    let name = entry.file_name().to_string_lossy().into_owned();
+                    let size = tree_size(&entry.path(), &meter, &cancel);
+                    if tx.send((name, size)).is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+        LargestChildren {
+            dir,
+            items: Vec::new(),
+            rx,
+            cancel,
+        }
+    }
+
+    /// ワーカーから届いた結果を取り込み、大きい順に並べ直す
+    pub fn poll(&mut self) {
+        let mut received = false;
+        while let Ok(item) = self.rx.try_recv() {
+            self.items.push(item);
+            received = true;
+        }
+        if received {
+            self.items.sort_by_key(|item| Reverse(item.1));
+        }
+    }
+}
+
+impl Drop for LargestChildren {
+    fn drop(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+/// シンボリックリンクは辿らずに `path` 以下のファイルサイズを合計する
+pub fn tree_size(path: &Path, meter: &JobMeter, cancel: &AtomicBool) -> u64 {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(_) => return 0,
+    };
+    if !metadata.is_dir() {
+        meter.add(metadata.len());
+        return metadata.len();
+    }
+    let entries = match fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(_) => return 0,
+    };
+    let mut total = 0;
+    for entry in entries.flatten() {
+        if cancel.load(Ordering::Relaxed) {
+            break;
+        }
+        total += tree_size(&entry.path(), meter, cancel);
+    }
+    total
+}
+
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 6] = ["B", "K", "M", "G", "T", "P"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    match unit {
+        0 => format!("{}{}", bytes, UNITS[0]),
+        _ => format!("{:.1}{}", size, UNITS[unit]),
+    }
+}
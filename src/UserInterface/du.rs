@@ -0,0 +1,408 @@
+use crate::UserInterface::disk::JobMeter;
+use crate::UserInterface::names;
+use crate::UserInterface::status::Severity;
+use crate::util::event::{Event, Job};
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use tui::widgets::ListState;
+
+pub struct DuNode {
+    /// 削除するときにパスを組み立て直すので、表示用に崩さずそのまま持つ
+    pub name: OsString,
+    pub size: u64,
+    /// 配下にあるエントリの数（自身は含まない）
+    pub count: u64,
+    pub is_dir: bool,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+}
+
+/// スキャン結果のツリー。ノードは `nodes` に追加していき、インデックスで参照する。
+/// 0 番がスキャンを開始したディレクトリ。
+pub struct DuTree {
+    pub nodes: Vec<DuNode>,
+}
+
+impl DuTree {
+    fn new(root: &Path) -> DuTree {
+        DuTree {
+            nodes: vec![DuNode {
+                name: root.as_os_str().to_os_string(),
+                size: 0,
+                count: 0,
+                is_dir: true,
+                parent: None,
+                children: Vec::new(),
+            }],
+        }
+    }
+
+    fn add(&mut self, parent: usize, name: OsString, size: u64, is_dir: bool) -> usize {
+        let id = self.nodes.len();
+        self.nodes.push(DuNode {
+            name,
+            size,
+            count: 0,
+            is_dir,
+            parent: Some(parent),
+            children: Vec::new(),
+        });
+        self.nodes[parent].children.push(id);
+        let mut ancestor = Some(parent);
+        while let Some(current) = ancestor {
+            self.nodes[current].size += size;
+            self.nodes[current].count += 1;
+            ancestor = self.nodes[current].parent;
+        }
+        id
+    }
+
+    /// ノードを親から切り離し、祖先のサイズと件数から差し引く
+    fn remove(&mut self, id: usize) {
+        let size = self.nodes[id].size;
+        let count = self.nodes[id].count + 1;
+        if let Some(parent) = self.nodes[id].parent.take() {
+            self.nodes[parent].children.retain(|child| *child != id);
+            let mut ancestor = Some(parent);
+            while let Some(current) = ancestor {
+                self.nodes[current].size = self.nodes[current].size.saturating_sub(size);
+                self.nodes[current].count = self.nodes[current].count.saturating_sub(count);
+                ancestor = self.nodes[current].parent;
+            }
+        }
+    }
+
+    /// 根までたどれるか。削除して切り離したノードの下は `false`。
+    fn is_attached(&self, mut id: usize) -> bool {
+        while id != 0 {
+            match self.nodes[id].parent {
+                Some(parent) => id = parent,
+                None => return false,
+            }
+        }
+        true
+    }
+
+    pub fn path(&self, root: &Path, id: usize) -> PathBuf {
+        let mut parts = Vec::new();
+        let mut current = id;
+        while let Some(parent) = self.nodes[current].parent {
+            parts.push(&self.nodes[current].name);
+            current = parent;
+        }
+        let mut path = root.to_path_buf();
+        for name in parts.iter().rev() {
+            path.push(name);
+        }
+        path
+    }
+
+    /// 子ノードをサイズの大きい順に返す
+    pub fn sorted_children(&self, id: usize) -> Vec<usize> {
+        let mut children = self.nodes[id].children.clone();
+        children.sort_by(|a, b| {
+            self.nodes[*b]
+                .size
+                .cmp(&self.nodes[*a].size)
+                .then_with(|| self.nodes[*a].name.cmp(&self.nodes[*b].name))
+        });
+        children
+    }
+}
+
+/// ncdu 風のディスク使用量ビュー。スキャンは別スレッドで進み、途中経過もそのまま表示できる。
+pub struct DuView {
+    pub root: PathBuf,
+    pub tree: Arc<Mutex<DuTree>>,
+    pub current: usize,
+    pub selected: Option<usize>,
+    pub state: ListState,
+    pub pending_delete: Option<usize>,
+    pub scanned: Arc<AtomicU64>,
+    done: Arc<AtomicBool>,
+    cancel: Arc<AtomicBool>,
+    /// 削除の結果を知らせる送り口
+    events: Option<mpsc::Sender<Event>>,
+    /// 実行中の削除の数
+    deleting: Arc<AtomicUsize>,
+}
+
+impl DuView {
+    pub fn start(root: PathBuf, meter: JobMeter, events: Option<mpsc::Sender<Event>>) -> DuView {
+        let tree = Arc::new(Mutex::new(DuTree::new(&root)));
+        let scanned = Arc::new(AtomicU64::new(0));
+        let done = Arc::new(AtomicBool::new(false));
+        let cancel = Arc::new(AtomicBool::new(false));
+        {
+            let root = root.clone();
+            let tree = tree.clone();
+            let scanned = scanned.clone();
+            let done = done.clone();
+            let cancel = cancel.clone();
+            thread::spawn(move || {
+                scan(root, &tree, &meter, &scanned, &cancel);
+                done.store(true, Ordering::Relaxed);
+            });
+        }
+        DuView {
+            root,
+            tree,
+            current: 0,
+            selected: None,
+            state: ListState::default(),
+            pending_delete: None,
+            scanned,
+            done,
+            cancel,
+            events,
+            deleting: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.done.load(Ordering::Relaxed)
+    }
+
+    /// スキャンか削除がまだ終わっていないか
+    #[cfg(test)]
+    pub fn is_busy(&self) -> bool {
+        !self.is_done() || self.deleting.load(Ordering::Relaxed) > 0
+    }
+
+    fn children(&self) -> Vec<usize> {
+        match self.tree.lock() {
+            Ok(tree) => tree.sorted_children(self.current),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    fn step(&mut self, forward: bool) {
+        let children = self.children();
+        if children.is_empty() {
+            self.selected = None;
+            return;
+        }
+        let position = self
+            .selected
+            .and_then(|id| children.iter().position(|child| *child == id));
+        let next = match (position, forward) {
+            (None, _) => 0,
+            (Some(i), true) => (i + 1) % children.len(),
+            (Some(0), false) => children.len() - 1,
+            (Some(i), false) => i - 1,
+        };
+        self.selected = Some(children[next]);
+    }
+
+    pub fn next(&mut self) {
+        self.step(true);
+    }
+
+    pub fn previous(&mut self) {
+        self.step(false);
+    }
+
+    /// 選択中のディレクトリに潜る
+    pub fn enter(&mut self) {
+        let target = match (self.selected, self.tree.lock()) {
+            (Some(id), Ok(tree)) if tree.nodes[id].is_dir => Some(id),
+            _ => None,
+        };
+        if let Some(id) = target {
+            self.current = id;
+            self.selected = None;
+            self.state = ListState::default();
+        }
+    }
+
+    /// 親ディレクトリに戻り、元いたディレクトリを選択する
+    pub fn leave(&mut self) {
+        let parent = match self.tree.lock() {
+            Ok(tree) => tree.nodes[self.current].parent,
+            Err(_) => None,
+        };
+        if let Some(parent) = parent {
+            self.selected = Some(self.current);
+            self.current = parent;
+            self.state = ListState::default();
+        }
+    }
+
+    pub fn request_delete(&mut self) {
+        self.pending_delete = self.selected;
+    }
+
+    pub fn cancel_delete(&mut self) {
+        self.pending_delete = None;
+    }
+
+    /// `request_delete` で指定したエントリをツリーから切り離し、別スレッドでディスクから削除する。
+    /// 切り離したエントリの下はスキャンも読まない。結果は `Job::Message` で知らせる。
+    pub fn confirm_delete(&mut self) -> io::Result<()> {
+        let id = match self.pending_delete.take() {
+            Some(id) => id,
+            None => return Ok(()),
+        };
+        let (path, name, is_dir) = match self.tree.lock() {
+            Ok(mut tree) => {
+                let target = (
+                    tree.path(&self.root, id),
+                    names::escape(&tree.nodes[id].name),
+                    tree.nodes[id].is_dir,
+                );
+                tree.remove(id);
+                target
+            }
+            Err(_) => return Err(io::Error::other("scan thread panicked")),
+        };
+        if self.selected == Some(id) {
+            self.selected = None;
+        }
+        let events = self.events.clone();
+        let deleting = self.deleting.clone();
+        deleting.fetch_add(1, Ordering::Relaxed);
+        thread::spawn(move || {
+            let result = match is_dir {
+                true => fs::remove_dir_all(&path),
+                false => fs::remove_file(&path),
+            };
+            let job = match result {
+                Ok(()) => Job::Message(Severity::Info, format!("削除しました: {}", name)),
+                Err(e) => Job::Message(Severity::Error, format!("{} を削除できません: {}", name, e)),
+            };
+            if let Some(events) = events {
+                let _ = events.send(Event::Job(job));
+            }
+            deleting.fetch_sub(1, Ordering::Relaxed);
+        });
+        Ok(())
+    }
+
+    pub fn name_of(&self, id: usize) -> String {
+        match self.tree.lock() {
+            Ok(tree) => names::escape(&tree.nodes[id].name),
+            Err(_) => String::new(),
+        }
+    }
+}
+
+impl Drop for DuView {
+    fn drop(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+/// ディレクトリを 1 つずつ読み、読み終えた分をまとめてツリーに反映する
+fn scan(
+    root: PathBuf,
+    tree: &Mutex<DuTree>,
+    meter: &JobMeter,
+    scanned: &AtomicU64,
+    cancel: &AtomicBool,
+) {
+    let mut stack = vec![(root, 0)];
+    while let Some((dir, id)) = stack.pop() {
+        if cancel.load(Ordering::Relaxed) {
+            return;
+        }
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        let mut batch = Vec::new();
+        for entry in entries.flatten() {
+            let metadata = match entry.path().symlink_metadata() {
+                Ok(metadata) => metadata,
+                Err(_) => continue,
+            };
+            let size = match metadata.is_dir() {
+                true => 0,
+                false => metadata.len(),
+            };
+            meter.add(size);
+            batch.push((entry.path(), entry.file_name(), size, metadata.is_dir()));
+        }
+        scanned.fetch_add(batch.len() as u64, Ordering::Relaxed);
+
+        let mut tree = match tree.lock() {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        // 読んでいる間に削除されたディレクトリ
+        if !tree.is_attached(id) {
+            continue;
+        }
+        for (path, name, size, is_dir) in batch {
+            let child = tree.add(id, name, size, is_dir);
+            if is_dir {
+                stack.push((path, child));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// root/{src/{main.rs (10), lib.rs (30)}, README (5), docs/guide (100)}
+    fn sample() -> (DuTree, [usize; 6]) {
+        let mut tree = DuTree::new(Path::new("root"));
+        let src = tree.add(0, OsString::from("src"), 0, true);
+        let main = tree.add(src, OsString::from("main.rs"), 10, false);
+        let lib = tree.add(src, OsString::from("lib.rs"), 30, false);
+        let readme = tree.add(0, OsString::from("README"), 5, false);
+        let docs = tree.add(0, OsString::from("docs"), 0, true);
+        let guide = tree.add(docs, OsString::from("guide"), 100, false);
+        (tree, [src, main, lib, readme, docs, guide])
+    }
+
+    #[test]
+    fn adding_nested_entries_updates_every_ancestor() {
+        let (tree, [src, main, ..]) = sample();
+        assert_eq!(tree.nodes[src].size, 40);
+        assert_eq!(tree.nodes[src].count, 2);
+        assert_eq!(tree.nodes[0].size, 145);
+        assert_eq!(tree.nodes[0].count, 6);
+        assert_eq!(tree.path(Path::new("/tmp/root"), main), Path::new("/tmp/root/src/main.rs"));
+    }
+
+    #[test]
+    fn removing_a_subtree_subtracts_from_ancestors() {
+        let (mut tree, [src, main, _, readme, docs, _]) = sample();
+        tree.remove(main);
+        assert_eq!(tree.nodes[src].size, 30);
+        assert_eq!(tree.nodes[src].count, 1);
+        tree.remove(src);
+        assert_eq!(tree.nodes[0].size, 105);
+        assert_eq!(tree.nodes[0].count, 3);
+        assert_eq!(tree.nodes[0].children, vec![readme, docs]);
+        assert!(!tree.is_attached(main));
+        assert!(tree.is_attached(docs));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn keeps_names_that_are_not_utf8() {
+        use std::os::unix::ffi::OsStrExt;
+        let mut tree = DuTree::new(Path::new("root"));
+        let name = std::ffi::OsStr::from_bytes(b"caf\xe9");
+        let id = tree.add(0, name.to_os_string(), 1, false);
+        assert_eq!(tree.path(Path::new("/tmp"), id), Path::new("/tmp").join(name));
+    }
+
+    #[test]
+    fn children_are_ordered_by_cumulative_size() {
+        let (mut tree, [src, main, lib, readme, docs, _]) = sample();
+        assert_eq!(tree.sorted_children(0), vec![docs, src, readme]);
+        assert_eq!(tree.sorted_children(src), vec![lib, main]);
+        // 同じサイズなら名前順
+        let extra = tree.add(0, OsString::from("AUTHORS"), 5, false);
+        assert_eq!(tree.sorted_children(0), vec![docs, src, extra, readme]);
+    }
+}
@@ -0,0 +1,58 @@
+use std::fmt::{self, Display, Formatter};
+use std::io;
+use std::path::PathBuf;
+
+/// 操作の失敗。ステータスバーとメッセージ履歴に表示される。
+#[derive(Debug)]
+pub enum AppError {
+    /// ファイル操作の失敗と対象のパス
+    Io(PathBuf, io::Error),
+    /// ショートカットの JSON が読めない
+    Shortcut(PathBuf, serde_json::Error),
+    /// ショートカットの先が存在しない
+    MissingShortcut(usize, PathBuf),
+    /// ショートカットの JSON に無い番号
+    UnknownShortcut(usize),
+    Open(PathBuf, io::Error),
+    Clipboard(io::Error),
+    NotADirectory(PathBuf),
+    /// `:` コマンドの誤り
+    Command(String),
+}
+
+impl Display for AppError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            AppError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            AppError::Shortcut(path, e) => {
+                write!(f, "ショートカット {} が読めません: {}", path.display(), e)
+            }
+            AppError::MissingShortcut(number, path) => {
+                write!(f, "ショートカット {} の {} はありません", number, path.display())
+            }
+            AppError::UnknownShortcut(number) => write!(f, "ショートカット {} はありません", number),
+            AppError::Open(path, e) => write!(f, "{} を開けません: {}", path.display(), e),
+            AppError::Clipboard(e) => write!(f, "コピーできません: {}", e),
+            AppError::NotADirectory(path) => {
+                write!(f, "{} はディレクトリではありません", path.display())
+            }
+            AppError::Command(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for AppError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AppError::Io(_, e) | AppError::Open(_, e) | AppError::Clipboard(e) => Some(e),
+            AppError::Shortcut(_, e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<String> for AppError {
+    fn from(message: String) -> AppError {
+        AppError::Command(message)
+    }
+}
@@ -0,0 +1,194 @@
+//! 端末なしで App を動かすテスト用の道具。
+//! 一時ディレクトリを起点に App を作り、`main` と同じようにイベントを渡して TestBackend に描画する。
+
+use crate::config::Config;
+use crate::UserInterface::du::DuView;
+use crate::UserInterface::keymap::KeyPress;
+use crate::UserInterface::{ui, App};
+use crate::util::event::{Event, Events};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+use tui::backend::TestBackend;
+use tui::Terminal;
+
+/// 読込やスキャンの完了を待つ上限
+const SETTLE_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct Harness {
+    pub app: App<'static>,
+    pub terminal: Terminal<TestBackend>,
+    /// 別スレッドの処理からの知らせを受ける。入力は `press` で直接渡す。
+    pub events: Events,
+    /// App の起点。Harness と一緒に消える。
+    pub dir: TempDir,
+}
+
+impl Harness {
+    /// `files` を作った一時ディレクトリで App を起動する。`/` で終わる名前はディレクトリ。
+    pub fn new(files: &[&str]) -> Harness {
+        Harness::with_config(files, Config::default())
+    }
+
+    pub fn with_config(files: &[&str], mut config: Config) -> Harness {
+        let dir = tempfile::tempdir().expect("temp dir");
+        // 利用者のデータディレクトリにある訪問記録を読まない
+        config.general.frecency.get_or_insert_with(|| PathBuf::from("frecency.json"));
+        for file in files {
+            create(dir.path(), file);
+        }
+        let events = Events::new();
+        let app = App::with_root("test", true, config, dir.path(), Some(events.sender()));
+        let terminal = Terminal::new(TestBackend::new(100, 30)).expect("test terminal");
+        let mut harness = Harness {
+            app,
+            terminal,
+            events,
+            dir,
+        };
+        harness.settle();
+        harness
+    }
+
+    pub fn root(&self) -> PathBuf {
+        self.dir.path().to_path_buf()
+    }
+
+    /// 空白区切りの打鍵（`j`、`enter`、`ctrl-d` など）を順に渡す
+    pub fn press(&mut self, keys: &str) -> &mut Harness {
+        for token in keys.split_whitespace() {
+            let key = KeyPress::parse(token).expect("key");
+            self.send(KeyEvent {
+                code: key.code,
+                modifiers: key.modifiers,
+            });
+        }
+        self
+    }
+
+    /// 入力欄に文字列を打つ
+    pub fn type_text(&mut self, text: &str) -> &mut Harness {
+        for c in text.chars() {
+            self.send(KeyEvent {
+                code: KeyCode::Char(c),
+                modifiers: KeyModifiers::NONE,
+            });
+        }
+        self
+    }
+
+    /// 端末の大きさを変えて描き直す
+    pub fn resize(&mut self, width: u16, height: u16) -> &mut Harness {
+        // TestBackend は clear で画面を消さないので、作り直して前の描画を残さない
+        self.terminal = Terminal::new(TestBackend::new(width, height)).expect("test terminal");
+        self.app.on_event(Event::Resize);
+        self.settle()
+    }
+
+    /// 画面の `column` 桁 `row` 行でマウスを操作する
+    pub fn mouse(&mut self, kind: MouseEventKind, column: u16, row: u16) -> &mut Harness {
+        self.app.on_event(Event::Mouse(MouseEvent {
+            kind,
+            column,
+            row,
+            modifiers: KeyModifiers::NONE,
+        }));
+        self.settle()
+    }
+
+    pub fn click(&mut self, column: u16, row: u16) -> &mut Harness {
+        self.mouse(MouseEventKind::Down(MouseButton::Left), column, row)
+    }
+
+    /// 画面上で `text` が最初に現れる位置（桁、行）
+    pub fn find(&self, text: &str) -> Option<(u16, u16)> {
+        self.screen().lines().enumerate().find_map(|(row, line)| {
+            line.find(text)
+                .map(|byte| (line[..byte].chars().count() as u16, row as u16))
+        })
+    }
+
+    fn send(&mut self, event: KeyEvent) {
+        self.app.on_event(Event::Key(event));
+        self.settle();
+    }
+
+    /// 別スレッドでの読込、使用量のスキャンや削除が終わるまで届いた知らせを処理し、描画する
+    pub fn settle(&mut self) -> &mut Harness {
+        let start = Instant::now();
+        while self.is_busy() {
+            assert!(start.elapsed() < SETTLE_TIMEOUT, "directory loading did not finish");
+            match self.events.next_timeout(Duration::from_millis(50)) {
+                Some(event) => self.app.on_event(event),
+                // 知らせを取りこぼしても tick で拾える
+                None => self.app.on_event(Event::Tick),
+            }
+        }
+        self.draw();
+        self
+    }
+
+    /// どこかのタブが読込中か、使用量アナライザが動いているか
+    fn is_busy(&self) -> bool {
+        let loading = self
+            .app
+            .panes
+            .iter()
+            .flat_map(|pane| pane.dirs.iter())
+            .any(|tab| tab.loader.is_some());
+        loading || self.app.du.as_ref().map(DuView::is_busy).unwrap_or(false)
+    }
+
+    pub fn draw(&mut self) {
+        let app = &mut self.app;
+        self.terminal.draw(|f| ui::draw(f, app)).expect("draw");
+    }
+
+    /// 画面を行ごとの文字列にする。全角文字の後ろには埋め草の空白が入る。
+    pub fn screen(&self) -> String {
+        let buffer = self.terminal.backend().buffer();
+        let width = buffer.area.width as usize;
+        buffer
+            .content
+            .chunks(width)
+            .map(|row| row.iter().map(|cell| cell.symbol.as_str()).collect::<String>())
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    /// フォーカス中のペインの名前の並び
+    pub fn names(&self) -> Vec<String> {
+        self.app
+            .focused()
+            .list
+            .items
+            .iter()
+            .map(|task| task.name())
+            .collect()
+    }
+
+    /// フォーカス中のペインでカーソルのあるエントリの名前
+    pub fn selected(&self) -> Option<String> {
+        self.app.focused().selected().map(|task| task.name())
+    }
+
+    pub fn assert_screen_contains(&self, text: &str) {
+        let screen = self.screen();
+        assert!(screen.contains(text), "`{}` not on screen:\n{}", text, screen);
+    }
+}
+
+fn create(root: &Path, file: &str) {
+    let path = root.join(file);
+    match file.ends_with('/') {
+        true => fs::create_dir_all(&path).expect("create dir"),
+        false => {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).expect("create dir");
+            }
+            fs::write(&path, file).expect("create file");
+        }
+    }
+}
@@ -0,0 +1,642 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Formatter};
+
+/// キーに割り当てられる操作
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Quit,
+    ToggleChart,
+    Usage,
+    Down,
+    Up,
+    PageDown,
+    PageUp,
+    Top,
+    Bottom,
+    Open,
+    Parent,
+    ShowAll,
+    FocusLeft,
+    FocusRight,
+    /// フォーカス中のペインの隣に同じディレクトリのペインを開く
+    SplitPane,
+    ClosePane,
+    PrevTab,
+    NextTab,
+    /// フォーカス中のペインに同じディレクトリのタブを開く
+    NewDirTab,
+    CloseDirTab,
+    PrevDirTab,
+    NextDirTab,
+    Search,
+    Command,
+    Palette,
+    /// よく訪れるディレクトリを数文字で探して移動する
+    Jump,
+    /// パスを入力して移動する
+    GoTo,
+    /// パンくずで上のディレクトリを選んで移動する
+    Breadcrumb,
+    CopyPath,
+    Mark,
+    UnmarkAll,
+    Delete,
+    /// 確認に答える（使用量アナライザの削除）
+    Confirm,
+    Help,
+    Log,
+    /// シェルに戻って一時停止する
+    Suspend,
+    Shortcut(u8),
+}
+
+const ACTIONS: [Action; 41] = [
+    Action::Quit,
+    Action::ToggleChart,
+    Action::Usage,
+    Action::Down,
+    Action::Up,
+    Action::PageDown,
+    Action::PageUp,
+    Action::Top,
+    Action::Bottom,
+    Action::Open,
+    Action::Parent,
+    Action::ShowAll,
+    Action::FocusLeft,
+    Action::FocusRight,
+    Action::SplitPane,
+    Action::ClosePane,
+    Action::PrevTab,
+    Action::NextTab,
+    Action::NewDirTab,
+    Action::CloseDirTab,
+    Action::PrevDirTab,
+    Action::NextDirTab,
+    Action::Search,
+    Action::Command,
+    Action::Palette,
+    Action::Jump,
+    Action::GoTo,
+    Action::Breadcrumb,
+    Action::CopyPath,
+    Action::Mark,
+    Action::UnmarkAll,
+    Action::Delete,
+    Action::Confirm,
+    Action::Help,
+    Action::Log,
+    Action::Suspend,
+    Action::Shortcut(1),
+    Action::Shortcut(2),
+    Action::Shortcut(3),
+    Action::Shortcut(4),
+    Action::Shortcut(5),
+];
+
+impl Action {
+    pub fn all() -> &'static [Action] {
+        &ACTIONS
+    }
+
+    /// 設定ファイルで使う名前
+    pub fn name(self) -> String {
+        let name = match self {
+            Action::Quit => "quit",
+            Action::ToggleChart => "toggle_chart",
+            Action::Usage => "usage",
+            Action::Down => "down",
+            Action::Up => "up",
+            Action::PageDown => "page_down",
+            Action::PageUp => "page_up",
+            Action::Top => "top",
+            Action::Bottom => "bottom",
+            Action::Open => "open",
+            Action::Parent => "parent",
+            Action::ShowAll => "show_all",
+            Action::FocusLeft => "focus_left",
+            Action::FocusRight => "focus_right",
+            Action::SplitPane => "split_pane",
+            Action::ClosePane => "close_pane",
+            Action::PrevTab => "prev_tab",
+            Action::NextTab => "next_tab",
+            Action::NewDirTab => "new_dir_tab",
+            Action::CloseDirTab => "close_dir_tab",
+            Action::PrevDirTab => "prev_dir_tab",
+            Action::NextDirTab => "next_dir_tab",
+            Action::Search => "search",
+            Action::Command => "command",
+            Action::Palette => "palette",
+            Action::Jump => "jump",
+            Action::GoTo => "go_to",
+            Action::Breadcrumb => "breadcrumb",
+            Action::CopyPath => "copy_path",
+            Action::Mark => "mark",
+            Action::UnmarkAll => "unmark_all",
+            Action::Delete => "delete",
+            Action::Confirm => "confirm",
+            Action::Help => "help",
+            Action::Log => "log",
+            Action::Suspend => "suspend",
+            Action::Shortcut(n) => return format!("shortcut_{}", n),
+        };
+        name.to_string()
+    }
+
+    pub fn from_name(name: &str) -> Option<Action> {
+        Action::all().iter().copied().find(|action| action.name() == name)
+    }
+
+    pub fn description(self) -> String {
+        let description = match self {
+            Action::Quit => "一覧を書き出して終了する",
+            Action::ToggleChart => "ディスク使用量の表示を切り替える",
+            Action::Usage => "使用量アナライザを開く／閉じる",
+            Action::Down => "下へ移動",
+            Action::Up => "上へ移動",
+            Action::PageDown => "4 行下へ移動",
+            Action::PageUp => "4 行上へ移動",
+            Action::Top => "先頭へ移動",
+            Action::Bottom => "末尾へ移動",
+            Action::Open => "ディレクトリに入る／ファイルを開く",
+            Action::Parent => "親ディレクトリへ移動",
+            Action::ShowAll => "絞り込みを解除する",
+            Action::FocusLeft => "左のペインにフォーカスする",
+            Action::FocusRight => "右のペインにフォーカスする",
+            Action::SplitPane => "ペインを分割する",
+            Action::ClosePane => "ペインを閉じる",
+            Action::PrevTab => "前のタブ",
+            Action::NextTab => "次のタブ",
+            Action::NewDirTab => "ペインにディレクトリのタブを開く",
+            Action::CloseDirTab => "ペインのタブを閉じる",
+            Action::PrevDirTab => "ペインの前のタブ",
+            Action::NextDirTab => "ペインの次のタブ",
+            Action::Search => "名前で絞り込む",
+            Action::Command => "コマンドを入力する",
+            Action::Palette => "操作を名前で探して実行する",
+            Action::Jump => "よく訪れるディレクトリへ移動する",
+            Action::GoTo => "パスを入力して移動する",
+            Action::Breadcrumb => "パンくずから上のディレクトリを選んで移動する",
+            Action::CopyPath => "パスをクリップボードにコピーする",
+            Action::Mark => "印を付ける／外して次へ",
+            Action::UnmarkAll => "すべての印を外す",
+            Action::Delete => "削除する（使用量アナライザ）",
+            Action::Confirm => "削除の確認に答える（ほかのキーで取り消し）",
+            Action::Help => "このヘルプを表示する",
+            Action::Log => "メッセージの履歴を表示する",
+            Action::Suspend => "一時停止してシェルに戻る",
+            Action::Shortcut(n) => return format!("ショートカット {} へ移動", n),
+        };
+        description.to_string()
+    }
+
+    fn default_keys(self) -> Vec<&'static str> {
+        match self {
+            Action::Quit => vec!["e"],
+            Action::ToggleChart => vec!["t"],
+            Action::Usage => vec!["u"],
+            Action::Down => vec!["j", "down"],
+            Action::Up => vec!["k", "up"],
+            Action::PageDown => vec!["ctrl-d"],
+            Action::PageUp => vec!["ctrl-u"],
+            Action::Top => vec!["gg"],
+            Action::Bottom => vec!["G"],
+            Action::Open => vec!["c", "enter"],
+            Action::Parent => vec!["q"],
+            Action::ShowAll => vec!["esc"],
+            Action::FocusLeft => vec!["h"],
+            Action::FocusRight => vec!["l"],
+            Action::SplitPane => vec!["ctrl-w v"],
+            Action::ClosePane => vec!["ctrl-w c"],
+            Action::PrevTab => vec!["left"],
+            Action::NextTab => vec!["right"],
+            Action::NewDirTab => vec!["ctrl-t"],
+            Action::CloseDirTab => vec!["ctrl-w x"],
+            Action::PrevDirTab => vec!["gT"],
+            Action::NextDirTab => vec!["gt"],
+            Action::Search => vec!["/"],
+            Action::Command => vec![":"],
+            Action::Palette => vec!["ctrl-p"],
+            Action::Jump => vec!["z"],
+            Action::GoTo => vec!["ctrl-l"],
+            Action::Breadcrumb => vec!["b"],
+            Action::CopyPath => vec!["ctrl-c"],
+            Action::Mark => vec!["space"],
+            Action::UnmarkAll => vec!["U"],
+            Action::Delete => vec!["d"],
+            Action::Confirm => vec!["y"],
+            Action::Help => vec!["?"],
+            Action::Log => vec!["L"],
+            Action::Suspend => vec!["ctrl-z"],
+            Action::Shortcut(1) => vec!["1"],
+            Action::Shortcut(2) => vec!["2"],
+            Action::Shortcut(3) => vec!["3"],
+            Action::Shortcut(4) => vec!["4"],
+            Action::Shortcut(5) => vec!["5"],
+            Action::Shortcut(_) => vec![],
+        }
+    }
+}
+
+/// 修飾キー付きの 1 打鍵
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyPress {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeyPress {
+    /// 文字キーの Shift は大文字かどうかで表されるので取り除いておく
+    pub fn from_event(event: KeyEvent) -> KeyPress {
+        let modifiers = match event.code {
+            KeyCode::Char(_) => event.modifiers - KeyModifiers::SHIFT,
+            _ => event.modifiers,
+        };
+        KeyPress {
+            code: event.code,
+            modifiers,
+        }
+    }
+
+    fn plain(c: char) -> KeyPress {
+        KeyPress {
+            code: KeyCode::Char(c),
+            modifiers: KeyModifiers::NONE,
+        }
+    }
+
+    fn is_plain_char(&self) -> bool {
+        matches!(self.code, KeyCode::Char(_)) && self.modifiers == KeyModifiers::NONE
+    }
+
+    /// `ctrl-d`、`alt-x`、`enter`、`f5` などの 1 打鍵を読む
+    pub fn parse(token: &str) -> Result<KeyPress, String> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = token;
+        loop {
+            let lower = rest.to_lowercase();
+            let (modifier, len) = if lower.starts_with("ctrl-") {
+                (KeyModifiers::CONTROL, 5)
+            } else if lower.starts_with("alt-") {
+                (KeyModifiers::ALT, 4)
+            } else if lower.starts_with("shift-") {
+                (KeyModifiers::SHIFT, 6)
+            } else {
+                break;
+            };
+            if rest.len() == len {
+                break;
+            }
+            modifiers |= modifier;
+            rest = &rest[len..];
+        }
+        let lower = rest.to_lowercase();
+        let named = NAMED_KEYS.iter().find(|(name, _)| *name == lower).map(|(_, code)| *code);
+        let code = match named {
+            Some(code) => code,
+            None => {
+                let mut chars = rest.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => KeyCode::Char(c),
+                    _ => match lower.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()) {
+                        Some(n) if (1..=12).contains(&n) => KeyCode::F(n),
+                        _ => return Err(format!("unknown key `{}`", token)),
+                    },
+                }
+            }
+        };
+        // 文字キーの Shift は押されても取り除くので、`shift-x` では決して一致しない
+        if let (KeyCode::Char(c), true) = (code, modifiers.contains(KeyModifiers::SHIFT)) {
+            return Err(format!(
+                "`{}`: write the shifted character (`{}`) instead of shift-",
+                token,
+                c.to_uppercase()
+            ));
+        }
+        Ok(KeyPress::from_event(KeyEvent { code, modifiers }))
+    }
+}
+
+impl Display for KeyPress {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "ctrl-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "alt-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            write!(f, "shift-")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "space"),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::F(n) => write!(f, "f{}", n),
+            KeyCode::Enter => write!(f, "enter"),
+            KeyCode::Esc => write!(f, "esc"),
+            KeyCode::Tab => write!(f, "tab"),
+            KeyCode::BackTab => write!(f, "backtab"),
+            KeyCode::Backspace => write!(f, "backspace"),
+            KeyCode::Delete => write!(f, "delete"),
+            KeyCode::Insert => write!(f, "insert"),
+            KeyCode::Up => write!(f, "up"),
+            KeyCode::Down => write!(f, "down"),
+            KeyCode::Left => write!(f, "left"),
+            KeyCode::Right => write!(f, "right"),
+            KeyCode::Home => write!(f, "home"),
+            KeyCode::End => write!(f, "end"),
+            KeyCode::PageUp => write!(f, "pageup"),
+            KeyCode::PageDown => write!(f, "pagedown"),
+            KeyCode::Null => write!(f, "null"),
+        }
+    }
+}
+
+/// 名前で書くキー
+const NAMED_KEYS: [(&str, KeyCode); 17] = [
+    ("enter", KeyCode::Enter),
+    ("esc", KeyCode::Esc),
+    ("tab", KeyCode::Tab),
+    ("backtab", KeyCode::BackTab),
+    ("backspace", KeyCode::Backspace),
+    ("delete", KeyCode::Delete),
+    ("del", KeyCode::Delete),
+    ("insert", KeyCode::Insert),
+    ("space", KeyCode::Char(' ')),
+    ("up", KeyCode::Up),
+    ("down", KeyCode::Down),
+    ("left", KeyCode::Left),
+    ("right", KeyCode::Right),
+    ("home", KeyCode::Home),
+    ("end", KeyCode::End),
+    ("pageup", KeyCode::PageUp),
+    ("pagedown", KeyCode::PageDown),
+];
+
+/// `token` がキー名 `name` の書きかけか、1 文字の抜け・余り・違い・入れ替えで済む打ち間違いか
+fn resembles(token: &str, name: &str) -> bool {
+    if name.starts_with(token) || token.starts_with(name) {
+        return true;
+    }
+    let (a, b): (Vec<char>, Vec<char>) = (token.chars().collect(), name.chars().collect());
+    let (short, long) = match a.len() <= b.len() {
+        true => (&a, &b),
+        false => (&b, &a),
+    };
+    match long.len() - short.len() {
+        0 => {
+            let diff: Vec<usize> = (0..short.len()).filter(|&i| short[i] != long[i]).collect();
+            match diff.as_slice() {
+                [_] => true,
+                [i, j] => *j == i + 1 && short[*i] == long[*j] && short[*j] == long[*i],
+                _ => false,
+            }
+        }
+        1 => (0..long.len()).any(|skip| {
+            long.iter()
+                .enumerate()
+                .filter(|(i, _)| *i != skip)
+                .map(|(_, c)| c)
+                .eq(short.iter())
+        }),
+        _ => false,
+    }
+}
+
+/// 打鍵の並び。`gg` のような文字だけの並びは続けて、それ以外は空白区切りで書く
+/// （例: `ctrl-w l`）。
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeySequence(pub Vec<KeyPress>);
+
+impl KeySequence {
+    pub fn parse(text: &str) -> Result<KeySequence, String> {
+        let mut keys = Vec::new();
+        for token in text.split_whitespace() {
+            match KeyPress::parse(token) {
+                Ok(key) => keys.push(key),
+                // `gg` のように英字だけの文字列は 1 文字ずつの並びとみなす。
+                // ただし `entr` のようにキー名の打ち間違いらしいものは誤りにする。
+                Err(e) => {
+                    let lower = token.to_lowercase();
+                    let letters = token.chars().all(|c| c.is_ascii_alphabetic());
+                    if !letters || NAMED_KEYS.iter().any(|(name, _)| resembles(&lower, name)) {
+                        return Err(e);
+                    }
+                    keys.extend(token.chars().map(KeyPress::plain));
+                }
+            }
+        }
+        match keys.is_empty() {
+            true => Err(String::from("empty key binding")),
+            false => Ok(KeySequence(keys)),
+        }
+    }
+
+    fn starts_with(&self, prefix: &[KeyPress]) -> bool {
+        self.0.len() >= prefix.len() && self.0[..prefix.len()] == *prefix
+    }
+}
+
+impl Display for KeySequence {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let compact = self.0.iter().all(|key| key.is_plain_char() && key.code != KeyCode::Char(' '));
+        for (i, key) in self.0.iter().enumerate() {
+            if i > 0 && !compact {
+                write!(f, " ")?;
+            }
+            write!(f, "{}", key)?;
+        }
+        Ok(())
+    }
+}
+
+/// 設定ファイルの `[keys]` の値。1 つでも配列でも書ける。
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(untagged)]
+pub enum KeyBinding {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl KeyBinding {
+    fn keys(&self) -> Vec<&str> {
+        match self {
+            KeyBinding::One(key) => vec![key.as_str()],
+            KeyBinding::Many(keys) => keys.iter().map(|key| key.as_str()).collect(),
+        }
+    }
+}
+
+/// 押されたキーを渡したときの結果
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Lookup {
+    Action(Action),
+    /// 複数打鍵の途中
+    Pending,
+    None,
+}
+
+/// キーと操作の対応表。複数打鍵の途中経過も持つ。
+pub struct Keymap {
+    bindings: Vec<(KeySequence, Action)>,
+    pending: Vec<KeyPress>,
+}
+
+impl Default for Keymap {
+    fn default() -> Keymap {
+        Keymap::new(&BTreeMap::new()).expect("default key bindings are valid")
+    }
+}
+
+impl Keymap {
+    /// 既定の割り当てに設定ファイルの割り当てを重ねる。設定した操作は既定のキーを失い、
+    /// 設定したキーと重なる既定の割り当ても外れる。
+    pub fn new(overrides: &BTreeMap<String, KeyBinding>) -> Result<Keymap, String> {
+        let mut configured: Vec<(KeySequence, Action)> = Vec::new();
+        for (name, binding) in overrides {
+            let action = Action::from_name(name)
+                .ok_or_else(|| format!("[keys] unknown action `{}`", name))?;
+            for key in binding.keys() {
+                let sequence =
+                    KeySequence::parse(key).map_err(|e| format!("[keys] {}: {}", name, e))?;
+                if let Some((_, other)) = configured.iter().find(|(s, _)| conflicts(s, &sequence)) {
+                    return Err(format!(
+                        "[keys] `{}` for {} conflicts with a key of {}",
+                        sequence,
+                        name,
+                        other.name()
+                    ));
+                }
+                configured.push((sequence, action));
+            }
+        }
+
+        let mut bindings = Vec::new();
+        for action in Action::all() {
+            if configured.iter().any(|(_, a)| a == action) {
+                continue;
+            }
+            for key in action.default_keys() {
+                let sequence = KeySequence::parse(key)?;
+                if !configured.iter().any(|(s, _)| conflicts(s, &sequence)) {
+                    bindings.push((sequence, *action));
+                }
+            }
+        }
+        bindings.extend(configured);
+        Ok(Keymap {
+            bindings,
+            pending: Vec::new(),
+        })
+    }
+
+    /// 打鍵を 1 つ渡す。割り当てのある並びが完成すれば操作を返す。
+    pub fn feed(&mut self, event: KeyEvent) -> Lookup {
+        self.pending.push(KeyPress::from_event(event));
+        if let Some((_, action)) = self.bindings.iter().find(|(s, _)| s.0 == self.pending) {
+            self.pending.clear();
+            return Lookup::Action(*action);
+        }
+        if self.bindings.iter().any(|(s, _)| s.starts_with(&self.pending)) {
+            return Lookup::Pending;
+        }
+        self.pending.clear();
+        Lookup::None
+    }
+
+    /// 入力途中の打鍵（ステータス表示用）
+    pub fn pending(&self) -> String {
+        KeySequence(self.pending.clone()).to_string()
+    }
+
+    pub fn keys_for(&self, action: Action) -> Vec<String> {
+        self.bindings
+            .iter()
+            .filter(|(_, a)| *a == action)
+            .map(|(s, _)| s.to_string())
+            .collect()
+    }
+
+    /// ヘルプ用に、割り当てのある操作とそのキーを並べる
+    pub fn help(&self) -> Vec<(String, String)> {
+        Action::all()
+            .iter()
+            .map(|action| (self.keys_for(*action), *action))
+            .filter(|(keys, _)| !keys.is_empty())
+            .map(|(keys, action)| (keys.join(", "), action.description()))
+            .collect()
+    }
+}
+
+/// どちらかがもう一方の前半と一致すると、打鍵の途中で区別できない
+fn conflicts(a: &KeySequence, b: &KeySequence) -> bool {
+    a.starts_with(&b.0) || b.starts_with(&a.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(token: &str) -> KeyEvent {
+        let key = KeyPress::parse(token).unwrap();
+        KeyEvent {
+            code: key.code,
+            modifiers: key.modifiers,
+        }
+    }
+
+    fn overrides(pairs: &[(&str, &str)]) -> BTreeMap<String, KeyBinding> {
+        pairs
+            .iter()
+            .map(|(action, key)| (action.to_string(), KeyBinding::One(key.to_string())))
+            .collect()
+    }
+
+    #[test]
+    fn parses_sequences() {
+        let gg = KeySequence::parse("gg").unwrap();
+        assert_eq!(gg.to_string(), "gg");
+        let window = KeySequence::parse("ctrl-w l").unwrap();
+        assert_eq!(window.0.len(), 2);
+        assert_eq!(window.to_string(), "ctrl-w l");
+        assert!(KeySequence::parse("ctrl-nothing").is_err());
+        assert_eq!(KeySequence::parse("g g").unwrap(), gg);
+        assert_eq!(KeySequence::parse("gT").unwrap().to_string(), "gT");
+        // キー名の打ち間違いは文字の並びにしない
+        for typo in ["entr", "spce", "ecs", "pgaeup", "f13"] {
+            assert!(KeySequence::parse(typo).is_err(), "{}", typo);
+        }
+        // 文字キーの Shift は大文字で書く
+        assert!(KeySequence::parse("shift-x").is_err());
+        assert_eq!(KeySequence::parse("X").unwrap().to_string(), "X");
+        assert_eq!(KeySequence::parse("shift-tab").unwrap().to_string(), "shift-tab");
+    }
+
+    #[test]
+    fn feeds_multi_key_sequences() {
+        let mut keymap = Keymap::default();
+        assert_eq!(keymap.feed(key("g")), Lookup::Pending);
+        assert_eq!(keymap.pending(), "g");
+        assert_eq!(keymap.feed(key("g")), Lookup::Action(Action::Top));
+        assert_eq!(keymap.feed(key("g")), Lookup::Pending);
+        assert_eq!(keymap.feed(key("x")), Lookup::None);
+        assert_eq!(keymap.feed(key("j")), Lookup::Action(Action::Down));
+    }
+
+    #[test]
+    fn overrides_replace_defaults() {
+        let mut keymap = Keymap::new(&overrides(&[("quit", "q")])).unwrap();
+        assert_eq!(keymap.keys_for(Action::Quit), vec!["q"]);
+        // 既定で q だった parent は外れる
+        assert!(keymap.keys_for(Action::Parent).is_empty());
+        assert_eq!(keymap.feed(key("e")), Lookup::None);
+    }
+
+    #[test]
+    fn rejects_conflicting_overrides() {
+        assert!(Keymap::new(&overrides(&[("quit", "x"), ("top", "x")])).is_err());
+        assert!(Keymap::new(&overrides(&[("quit", "x"), ("top", "x y")])).is_err());
+        assert!(Keymap::new(&overrides(&[("nothing", "x")])).is_err());
+    }
+}
@@ -0,0 +1,145 @@
+use crate::util::event::{Event, Job};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+
+/// 1 回に UI スレッドへ送るエントリ数
+const BATCH: usize = 256;
+
+/// 読み込んだエントリ
+pub struct Entry {
+    pub path: PathBuf,
+    /// シンボリックリンクは辿った先のメタデータ
+    pub metadata: Option<fs::Metadata>,
+    pub is_symlink: bool,
+}
+
+enum Loaded {
+    Batch(Vec<Entry>),
+    Failed(String),
+}
+
+/// 読込結果をどう一覧に反映するか
+#[derive(Clone, Copy, PartialEq)]
+pub enum LoadMode {
+    /// 一覧を空にして、届いた分から順に表示する（ディレクトリ移動時）
+    Stream,
+    /// 読み終わるまで今の一覧を残し、最後に丸ごと差し替える（再読込時）
+    Replace,
+}
+
+/// ディレクトリを別スレッドで読み、結果を少しずつ受け渡す。
+/// 破棄されると読込を打ち切る。
+pub struct DirLoader {
+    pub dir: PathBuf,
+    pub mode: LoadMode,
+    /// 読み終わったときにカーソルを合わせるエントリ
+    pub select: Option<PathBuf>,
+    /// `select` が見つからなかったときのカーソル位置
+    pub fallback: Option<usize>,
+    pub loaded: usize,
+    pub error: Option<String>,
+    buffer: Vec<Entry>,
+    done: bool,
+    rx: mpsc::Receiver<Loaded>,
+    cancel: Arc<AtomicBool>,
+}
+
+impl DirLoader {
+    /// `waker` があれば、エントリを送るたびにメインループを起こす
+    pub fn start(dir: PathBuf, mode: LoadMode, waker: Option<mpsc::Sender<Event>>) -> DirLoader {
+        let (tx, rx) = mpsc::channel();
+        let wake = move || {
+            if let Some(waker) = &waker {
+                let _ = waker.send(Event::Job(Job::Progress));
+            }
+        };
+        let cancel = Arc::new(AtomicBool::new(false));
+        {
+            let dir = dir.clone();
+            let cancel = cancel.clone();
+            thread::spawn(move || {
+                let entries = match fs::read_dir(&dir) {
+                    Ok(entries) => entries,
+                    Err(e) => {
+                        let _ = tx.send(Loaded::Failed(e.to_string()));
+                        drop(tx);
+                        wake();
+                        return;
+                    }
+                };
+                let mut batch = Vec::with_capacity(BATCH);
+                for entry in entries.flatten() {
+                    if cancel.load(Ordering::Relaxed) {
+                        return;
+                    }
+                    let path = entry.path();
+                    let metadata = fs::metadata(&path).or_else(|_| entry.metadata()).ok();
+                    let is_symlink = entry.file_type().map(|t| t.is_symlink()).unwrap_or(false);
+                    batch.push(Entry {
+                        path,
+                        metadata,
+                        is_symlink,
+                    });
+                    if batch.len() == BATCH {
+                        let full = std::mem::replace(&mut batch, Vec::with_capacity(BATCH));
+                        if tx.send(Loaded::Batch(full)).is_err() {
+                            return;
+                        }
+                        wake();
+                    }
+                }
+                let _ = tx.send(Loaded::Batch(batch));
+                // 読み終わりが伝わるように、送り口を閉じてから起こす
+                drop(tx);
+                wake();
+            });
+        }
+        DirLoader {
+            dir,
+            mode,
+            select: None,
+            fallback: None,
+            loaded: 0,
+            error: None,
+            buffer: Vec::new(),
+            done: false,
+            rx,
+            cancel,
+        }
+    }
+
+    /// ここまでに届いたエントリを受け取る。`Replace` では読み終わるまで空を返す。
+    pub fn poll(&mut self) -> Vec<Entry> {
+        loop {
+            match self.rx.try_recv() {
+                Ok(Loaded::Batch(batch)) => {
+                    self.loaded += batch.len();
+                    self.buffer.extend(batch);
+                }
+                Ok(Loaded::Failed(e)) => self.error = Some(e),
+                Err(mpsc::TryRecvError::Empty) => break,
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    self.done = true;
+                    break;
+                }
+            }
+        }
+        match (self.mode, self.done) {
+            (LoadMode::Replace, false) => Vec::new(),
+            _ => std::mem::take(&mut self.buffer),
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+}
+
+impl Drop for DirLoader {
+    fn drop(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
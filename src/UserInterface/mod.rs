@@ -1,4 +1,21 @@
 mod app;
+pub mod command;
+pub mod disk;
+pub mod du;
+pub mod error;
+#[cfg(test)]
+pub mod harness;
+pub mod keymap;
+pub mod loader;
+pub mod mouse;
+pub mod names;
+pub mod opener;
+pub mod pane;
+pub mod platform;
+pub mod prompt;
+pub mod status;
+pub mod tasks;
+pub mod theme;
 pub mod ui;
-pub use app::App;
-pub use app::Task;
+pub mod watch;
+pub use app::{read_listing, write_listing, App};
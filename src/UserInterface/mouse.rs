@@ -0,0 +1,114 @@
+use std::time::{Duration, Instant};
+use tui::layout::Rect;
+
+/// 2 回目のクリックをダブルクリックとみなす間隔
+const DOUBLE_CLICK: Duration = Duration::from_millis(400);
+/// ホイール 1 段で動かす行数
+pub const WHEEL_LINES: usize = 3;
+
+/// マウスが指しているもの
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Target {
+    Tab(usize),
+    /// ペインの枠に並ぶディレクトリのタブ（ペイン、タブ）
+    PaneTab(usize, usize),
+    /// ペインのタイトルのパンくず（ペイン、根から何番目の区切りか）
+    Crumb(usize, usize),
+    /// ペインと、枠の内側で上から何行目か（枠の上なら None）
+    Pane(usize, Option<usize>),
+    None,
+}
+
+/// 直前の描画で部品を置いた場所。描画のたびに作り直す。
+#[derive(Default)]
+pub struct HitMap {
+    pub tabs: Vec<(Rect, usize)>,
+    pub pane_tabs: Vec<(Rect, usize, usize)>,
+    pub crumbs: Vec<(Rect, usize, usize)>,
+    pub panes: Vec<(Rect, usize)>,
+    /// ディスク情報を出した場所。出していなければ `None`。
+    pub disk_info: Option<Rect>,
+}
+
+impl HitMap {
+    pub fn target(&self, column: u16, row: u16) -> Target {
+        if let Some((_, index)) = self.tabs.iter().find(|(area, _)| contains(area, column, row)) {
+            return Target::Tab(*index);
+        }
+        if let Some((_, pane, tab)) = self.pane_tabs.iter().find(|(area, _, _)| contains(area, column, row)) {
+            return Target::PaneTab(*pane, *tab);
+        }
+        if let Some((_, pane, crumb)) = self.crumbs.iter().find(|(area, _, _)| contains(area, column, row)) {
+            return Target::Crumb(*pane, *crumb);
+        }
+        match self.panes.iter().find(|(area, _)| contains(area, column, row)) {
+            Some((area, index)) => {
+                // 枠の内側だけを行として数える
+                let inner = row > area.y && row + 1 < area.bottom();
+                match inner {
+                    true => Target::Pane(*index, Some((row - area.y - 1) as usize)),
+                    false => Target::Pane(*index, None),
+                }
+            }
+            None => Target::None,
+        }
+    }
+}
+
+fn contains(area: &Rect, column: u16, row: u16) -> bool {
+    column >= area.x && column < area.right() && row >= area.y && row < area.bottom()
+}
+
+/// 同じ行を続けてクリックしたかを調べる
+#[derive(Default)]
+pub struct ClickTracker {
+    last: Option<(usize, usize, Instant)>,
+}
+
+impl ClickTracker {
+    /// クリックを記録し、ダブルクリックなら true を返す
+    pub fn click(&mut self, pane: usize, item: usize) -> bool {
+        let now = Instant::now();
+        let double = matches!(self.last, Some((p, i, at)) if p == pane && i == item && now - at < DOUBLE_CLICK);
+        // 3 回目は新しいクリックとして数える
+        self.last = match double {
+            true => None,
+            false => Some((pane, item, now)),
+        };
+        double
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_tabs_and_pane_rows() {
+        let hits = HitMap {
+            tabs: vec![(Rect::new(1, 1, 4, 1), 0), (Rect::new(8, 1, 4, 1), 1)],
+            pane_tabs: vec![(Rect::new(21, 12, 5, 1), 1, 2)],
+            crumbs: vec![(Rect::new(1, 3, 1, 1), 0, 0), (Rect::new(2, 3, 4, 1), 0, 1)],
+            panes: vec![(Rect::new(0, 3, 20, 10), 0), (Rect::new(20, 3, 20, 10), 1)],
+            disk_info: None,
+        };
+        assert_eq!(hits.target(9, 1), Target::Tab(1));
+        assert_eq!(hits.target(6, 1), Target::None);
+        assert_eq!(hits.target(5, 4), Target::Pane(0, Some(0)));
+        assert_eq!(hits.target(25, 7), Target::Pane(1, Some(3)));
+        assert_eq!(hits.target(25, 12), Target::PaneTab(1, 2));
+        assert_eq!(hits.target(3, 3), Target::Crumb(0, 1));
+        assert_eq!(hits.target(8, 3), Target::Pane(0, None));
+        assert_eq!(hits.target(28, 12), Target::Pane(1, None));
+    }
+
+    #[test]
+    fn second_click_on_the_same_row_is_a_double_click() {
+        let mut clicks = ClickTracker::default();
+        assert!(!clicks.click(0, 2));
+        assert!(clicks.click(0, 2));
+        assert!(!clicks.click(0, 2));
+        assert!(!clicks.click(1, 2));
+        assert!(!clicks.click(0, 2));
+    }
+}
@@ -0,0 +1,217 @@
+use std::ffi::OsStr;
+use std::io::{self, Write};
+use std::path::{Component, Path, PathBuf};
+use unicode_width::UnicodeWidthChar;
+
+/// ファイル名を表示用の文字列にする。
+/// UTF-8 として読めない部分と制御文字はエスケープして、元の名前が分かるようにする。
+#[cfg(unix)]
+pub fn escape(name: &OsStr) -> String {
+    use std::os::unix::ffi::OsStrExt;
+    let mut out = String::new();
+    for chunk in name.as_bytes().utf8_chunks() {
+        push_escaped(&mut out, chunk.valid());
+        for byte in chunk.invalid() {
+            out.push_str(&format!("\\x{:02x}", byte));
+        }
+    }
+    out
+}
+
+/// Windows では対になっていないサロゲートが UTF-8 にできない
+#[cfg(windows)]
+pub fn escape(name: &OsStr) -> String {
+    use std::os::windows::ffi::OsStrExt;
+    let mut out = String::new();
+    for unit in char::decode_utf16(name.encode_wide()) {
+        match unit {
+            Ok(c) => push_escaped(&mut out, c.encode_utf8(&mut [0; 4])),
+            Err(e) => out.push_str(&format!("\\u{{{:04x}}}", e.unpaired_surrogate())),
+        }
+    }
+    out
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn escape(name: &OsStr) -> String {
+    name.to_string_lossy().into_owned()
+}
+
+/// パスをそのままのバイト列で書く（UTF-8 でない名前も壊さない）。表示用の `escape` は通さない。
+pub fn write_path(out: &mut dyn Write, path: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        out.write_all(path.as_os_str().as_bytes())
+    }
+    #[cfg(not(unix))]
+    {
+        out.write_all(path.to_string_lossy().as_bytes())
+    }
+}
+
+fn push_escaped(out: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            // Unix ではエスケープと区別するため `\` も重ねる（Windows では区切り文字）
+            '\\' if cfg!(unix) => out.push_str("\\\\"),
+            c if c.is_control() => out.extend(c.escape_default()),
+            c => out.push(c),
+        }
+    }
+}
+
+/// パス全体をエスケープして表示する
+pub fn escape_path(path: &Path) -> String {
+    escape(path.as_os_str())
+}
+
+/// シンボリックリンクは辿らずに、パスの上で `.` と `..` を解決する。根より上へは戻らない。
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut normal = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normal.pop();
+            }
+            other => normal.push(other),
+        }
+    }
+    normal
+}
+
+/// パンくずの 1 区切り
+#[derive(Debug, PartialEq)]
+pub struct Crumb {
+    pub label: String,
+    /// 根からこの区切りまでのパス
+    pub path: PathBuf,
+}
+
+/// `dir` を根から順に区切る。根（`/` や `C:\`）はそのまま、それ以外は名前だけを表示する。
+pub fn breadcrumb(dir: &Path) -> Vec<Crumb> {
+    let mut crumbs: Vec<Crumb> = dir
+        .ancestors()
+        .filter(|ancestor| !ancestor.as_os_str().is_empty())
+        .map(|ancestor| Crumb {
+            label: match ancestor.file_name() {
+                Some(name) => escape(name),
+                None => escape_path(ancestor),
+            },
+            path: ancestor.to_path_buf(),
+        })
+        .collect();
+    crumbs.reverse();
+    crumbs
+}
+
+/// `base` から見た `path` の相対パス。ドライブが違うなど辿れなければ `path` のまま。
+pub fn relative(path: &Path, base: &Path) -> PathBuf {
+    let mut path_components = path.components().peekable();
+    let mut base_components = base.components().peekable();
+    // 共通の先頭部分を読み飛ばす
+    while let (Some(a), Some(b)) = (path_components.peek(), base_components.peek()) {
+        match a == b {
+            true => {
+                path_components.next();
+                base_components.next();
+            }
+            false => break,
+        }
+    }
+    let mut result = PathBuf::new();
+    for component in base_components {
+        match component {
+            Component::Normal(_) => result.push(".."),
+            Component::CurDir => {}
+            // 根やドライブが違う
+            _ => return path.to_path_buf(),
+        }
+    }
+    for component in path_components {
+        result.push(component);
+    }
+    match result.as_os_str().is_empty() {
+        true => PathBuf::from("."),
+        false => result,
+    }
+}
+
+/// 表示幅が `width` 桁を超えるなら、真ん中を `…` にして先頭と末尾（拡張子）を残す
+pub fn middle_ellipsis(text: &str, width: usize) -> String {
+    let char_width = |c: char| c.width().unwrap_or(0);
+    if text.chars().map(char_width).sum::<usize>() <= width {
+        return String::from(text);
+    }
+    if width == 0 {
+        return String::new();
+    }
+    // `…` の 1 桁を除いた残りを前後で分ける
+    let tail_width = (width - 1) / 2;
+    let head_width = width - 1 - tail_width;
+    let mut head = String::new();
+    let mut used = 0;
+    for c in text.chars() {
+        if used + char_width(c) > head_width {
+            break;
+        }
+        used += char_width(c);
+        head.push(c);
+    }
+    let mut tail = Vec::new();
+    let mut used = 0;
+    for c in text.chars().rev() {
+        if used + char_width(c) > tail_width {
+            break;
+        }
+        used += char_width(c);
+        tail.push(c);
+    }
+    head.push('…');
+    head.extend(tail.into_iter().rev());
+    head
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn escapes_invalid_utf8_and_control_characters() {
+        use std::os::unix::ffi::OsStrExt;
+        assert_eq!(escape(OsStr::from_bytes(b"a\xffb")), "a\\xffb");
+        assert_eq!(escape(OsStr::new("a\\b\n")), "a\\\\b\\n");
+        assert_eq!(escape(OsStr::new("日本語.txt")), "日本語.txt");
+    }
+
+    #[test]
+    fn relative_walks_up_to_the_common_ancestor() {
+        let root = std::env::temp_dir();
+        assert_eq!(relative(&root.join("a/b"), &root.join("c")), Path::new("..").join("a").join("b"));
+        assert_eq!(relative(&root.join("a/b"), &root), Path::new("a").join("b"));
+        assert_eq!(relative(&root, &root), Path::new("."));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn breadcrumb_lists_ancestors_from_the_root() {
+        let labels: Vec<String> = breadcrumb(Path::new("/usr/local/bin"))
+            .into_iter()
+            .map(|crumb| crumb.label)
+            .collect();
+        assert_eq!(labels, vec!["/", "usr", "local", "bin"]);
+        assert_eq!(breadcrumb(Path::new("/usr/local"))[1].path, Path::new("/usr"));
+        assert_eq!(breadcrumb(Path::new("/")).len(), 1);
+    }
+
+    #[test]
+    fn middle_ellipsis_keeps_both_ends() {
+        assert_eq!(middle_ellipsis("short.txt", 20), "short.txt");
+        assert_eq!(middle_ellipsis("a_very_long_name.txt", 11), "a_ver…e.txt");
+        assert_eq!(middle_ellipsis("日本語のファイル.txt", 10), "日本….txt");
+        assert_eq!(middle_ellipsis("abc", 1), "…");
+        assert_eq!(middle_ellipsis("abc", 0), "");
+    }
+}
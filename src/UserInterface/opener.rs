@@ -0,0 +1,24 @@
+use crate::config::OpenerRule;
+use crate::UserInterface::platform;
+use std::io;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+
+/// 設定の `[[openers]]` で拡張子に合うコマンドがあればそれで、無ければ OS の既定のアプリで開く
+pub fn open(path: &Path, rules: &[OpenerRule]) -> io::Result<()> {
+    match rules.iter().find(|rule| rule.matches(path)) {
+        Some(rule) => {
+            let mut child = Command::new(&rule.command)
+                .args(&rule.args)
+                .arg(path)
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()?;
+            thread::spawn(move || child.wait());
+            Ok(())
+        }
+        None => platform::open_default(path),
+    }
+}
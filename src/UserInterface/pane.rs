@@ -0,0 +1,188 @@
+use crate::config::View;
+use crate::util::{StatefulList, TabsState};
+use crate::UserInterface::app::{matches_filter, sort_tasks, Task};
+use crate::UserInterface::disk::DiskUsage;
+use crate::UserInterface::loader::{DirLoader, LoadMode};
+use crate::UserInterface::names;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+/// ペインの中のディレクトリのタブ 1 つ分
+pub struct DirTab {
+    /// 表示しているディレクトリ。ドライブの一覧（Windows）なら None。
+    pub dir: Option<PathBuf>,
+    pub list: StatefulList<Task>,
+    /// 検索による絞り込み
+    pub filter: Option<String>,
+    pub loader: Option<DirLoader>,
+    /// 印を付けたエントリ
+    pub marks: BTreeSet<PathBuf>,
+}
+
+impl DirTab {
+    pub fn new() -> DirTab {
+        DirTab {
+            dir: None,
+            list: StatefulList::new(),
+            filter: None,
+            loader: None,
+            marks: BTreeSet::new(),
+        }
+    }
+
+    /// 表示しているディレクトリ。空のディレクトリでも分かる。
+    pub fn dir(&self) -> Option<PathBuf> {
+        self.dir.clone()
+    }
+
+    /// `path` のエントリにカーソルを置く。読込中なら読み終わったときに置く。
+    pub fn select_path(&mut self, path: PathBuf) {
+        match self.loader.as_mut() {
+            Some(loader) => loader.select = Some(path),
+            None => {
+                let position = self.list.items.iter().position(|task| task.path == path);
+                if position.is_some() {
+                    self.list.state.select(position);
+                }
+            }
+        }
+    }
+
+    /// カーソルのあるエントリ
+    pub fn selected(&self) -> Option<&Task> {
+        self.list.state.selected().and_then(|i| self.list.items.get(i))
+    }
+
+    /// 届いた分のエントリを一覧に反映する。読み終わったら並べ替え、読めなかった理由があれば返す。
+    pub fn poll(&mut self, view: &View) -> Option<String> {
+        let loader = self.loader.as_mut()?;
+        let mut tasks: Vec<Task> = loader
+            .poll()
+            .into_iter()
+            .map(|entry| Task {
+                is_symlink: entry.is_symlink,
+                ..Task::with_metadata(entry.path, entry.metadata.as_ref())
+            })
+            .filter(|task| view.show_hidden || !task.hidden)
+            .collect();
+        if let Some(filter) = &self.filter {
+            tasks.retain(|task| matches_filter(task, filter));
+        }
+        match (loader.mode, loader.is_done()) {
+            (LoadMode::Stream, _) => self.list.items.extend(tasks),
+            (LoadMode::Replace, true) => self.list = StatefulList::with_items(tasks),
+            (LoadMode::Replace, false) => {}
+        }
+        if !loader.is_done() {
+            return None;
+        }
+        let error = loader
+            .error
+            .take()
+            .map(|e| format!("{} を読めません: {}", loader.dir.display(), e));
+
+        // 並べ替えてもカーソルのあるエントリは変えない
+        let list = &mut self.list;
+        let current = list
+            .state
+            .selected()
+            .and_then(|i| list.items.get(i))
+            .map(|task| task.path.clone());
+        sort_tasks(&mut list.items, view);
+        list.invalidate();
+        let position = match current.as_ref().or(loader.select.as_ref()) {
+            Some(name) => list
+                .items
+                .iter()
+                .position(|task| task.path == *name)
+                .or_else(|| loader.fallback.map(|i| i.min(list.items.len().saturating_sub(1)))),
+            None => None,
+        };
+        if !list.items.is_empty() {
+            list.state.select(position);
+        }
+        // 消えたエントリの印は外す
+        let items = &list.items;
+        self.marks.retain(|name| items.iter().any(|task| &task.path == name));
+        self.loader = None;
+        error
+    }
+}
+
+/// 並べて表示する一覧の 1 枠。ディレクトリごとのタブを持ち、選ばれているタブだけを表示する。
+pub struct Pane {
+    /// タブの見出し（ディレクトリ名）と選ばれているタブ。`dirs` と同じ順に並ぶ。
+    pub tabs: TabsState<String>,
+    pub dirs: Vec<DirTab>,
+    pub disk: Option<DiskUsage>,
+}
+
+impl Pane {
+    pub fn new() -> Pane {
+        Pane {
+            tabs: TabsState::new(vec![String::new()]),
+            dirs: vec![DirTab::new()],
+            disk: None,
+        }
+    }
+
+    pub fn current(&self) -> &DirTab {
+        &self.dirs[self.tabs.index]
+    }
+
+    pub fn current_mut(&mut self) -> &mut DirTab {
+        &mut self.dirs[self.tabs.index]
+    }
+
+    /// 今のタブの後ろに空のタブを開いて選ぶ
+    pub fn open_tab(&mut self) {
+        let index = self.tabs.index + 1;
+        self.dirs.insert(index, DirTab::new());
+        self.tabs.titles.insert(index, String::new());
+        self.tabs.index = index;
+    }
+
+    /// 今のタブを閉じる。最後の 1 つは閉じずに false を返す。
+    pub fn close_tab(&mut self) -> bool {
+        if self.dirs.len() == 1 {
+            return false;
+        }
+        self.dirs.remove(self.tabs.index);
+        self.tabs.titles.remove(self.tabs.index);
+        self.tabs.index = self.tabs.index.min(self.dirs.len() - 1);
+        true
+    }
+
+    /// 今のタブの見出しを `dir` に合わせる
+    pub fn set_title(&mut self, dir: &Path) {
+        self.tabs.titles[self.tabs.index] = match dir.file_name() {
+            Some(name) => names::escape(name),
+            None => names::escape_path(dir),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tabs_open_after_the_current_one_and_never_close_the_last() {
+        let mut pane = Pane::new();
+        pane.set_title(Path::new("/tmp/a"));
+        pane.open_tab();
+        pane.set_title(Path::new("/tmp/b"));
+        pane.tabs.index = 0;
+        pane.open_tab();
+        pane.set_title(Path::new("/tmp/c"));
+        assert_eq!(pane.tabs.titles, vec!["a", "c", "b"]);
+        assert_eq!(pane.tabs.index, 1);
+
+        assert!(pane.close_tab());
+        assert_eq!(pane.tabs.titles, vec!["a", "b"]);
+        assert_eq!(pane.tabs.index, 1);
+        assert!(pane.close_tab());
+        assert!(!pane.close_tab());
+        assert_eq!(pane.tabs.titles, vec!["a"]);
+    }
+}
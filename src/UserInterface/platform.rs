@@ -0,0 +1,114 @@
+//! OS ごとに異なる処理（既定のアプリで開く、クリップボード、ドライブの一覧）
+
+pub use self::imp::{copy_text, drives, open_default};
+
+#[cfg(windows)]
+mod imp {
+    use std::io;
+    use std::path::{Path, PathBuf};
+
+    winrt::import!(
+        dependencies
+            os
+        types
+            windows::system::Launcher
+            windows::application_model::data_transfer::*
+    );
+    use windows::application_model::data_transfer::{Clipboard, DataPackage};
+    use windows::storage::StorageFile;
+    use windows::system::Launcher;
+
+    /// WinRT のエラーを、呼び出し側で他のエラーと同じように扱えるようにする
+    fn winrt_error(e: winrt::Error) -> io::Error {
+        io::Error::other(format!("{:?}", e))
+    }
+
+    pub fn open_default(path: &Path) -> io::Result<()> {
+        let path = path.to_string_lossy();
+        // ファイルパスから `StorageFile` オブジェクトを取得
+        let file = StorageFile::get_file_from_path_async(path.as_ref())
+            .and_then(|operation| operation.get())
+            .map_err(winrt_error)?;
+
+        // 既定のプログラムを使用して `file` を開く
+        let launched = Launcher::launch_file_async(file)
+            .and_then(|operation| operation.get())
+            .map_err(winrt_error)?;
+        match launched {
+            true => Ok(()),
+            false => Err(io::Error::other("no application is associated with this file")),
+        }
+    }
+
+    pub fn copy_text(text: &str) -> io::Result<()> {
+        let content = DataPackage::new().map_err(winrt_error)?;
+        content.set_text(text.to_string()).map_err(winrt_error)?;
+
+        Clipboard::set_content(content).map_err(winrt_error)?;
+        Clipboard::flush().map_err(winrt_error)?;
+        Ok(())
+    }
+
+    /// 使えるドライブの根（`C:\` など）
+    pub fn drives() -> Vec<PathBuf> {
+        (b'A'..=b'Z')
+            .map(|letter| PathBuf::from(format!("{}:\\", letter as char)))
+            .filter(|root| root.exists())
+            .collect()
+    }
+}
+
+#[cfg(not(windows))]
+mod imp {
+    use std::io::{self, Write};
+    use std::path::{Path, PathBuf};
+    use std::process::{Command, Stdio};
+    use std::thread;
+
+    pub fn open_default(path: &Path) -> io::Result<()> {
+        let program = if cfg!(target_os = "macos") { "open" } else { "xdg-open" };
+        let mut child = Command::new(program)
+            .arg(path)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+        thread::spawn(move || child.wait());
+        Ok(())
+    }
+
+    /// 見つかったクリップボード用コマンドにテキストを渡す
+    pub fn copy_text(text: &str) -> io::Result<()> {
+        let candidates: [(&str, &[&str]); 4] = [
+            ("pbcopy", &[]),
+            ("wl-copy", &[]),
+            ("xclip", &["-selection", "clipboard"]),
+            ("xsel", &["--clipboard", "--input"]),
+        ];
+        let mut last = io::Error::new(io::ErrorKind::NotFound, "no clipboard command found");
+        for (program, args) in candidates.iter() {
+            let spawned = Command::new(program)
+                .args(*args)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn();
+            match spawned {
+                Ok(mut child) => {
+                    if let Some(mut stdin) = child.stdin.take() {
+                        stdin.write_all(text.as_bytes())?;
+                    }
+                    child.wait()?;
+                    return Ok(());
+                }
+                Err(e) => last = e,
+            }
+        }
+        Err(last)
+    }
+
+    /// `/` の上には何も無いので、ドライブの一覧は作らない
+    pub fn drives() -> Vec<PathBuf> {
+        Vec::new()
+    }
+}
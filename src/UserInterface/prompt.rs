@@ -0,0 +1,95 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// 入力欄の用途
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PromptKind {
+    Search,
+    Command,
+    Palette,
+    /// よく訪れるディレクトリへの移動
+    Jump,
+    /// パスを入力しての移動
+    GoTo,
+}
+
+impl PromptKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            PromptKind::Search => "/",
+            PromptKind::Command => ":",
+            PromptKind::Palette => "> ",
+            PromptKind::Jump => "z ",
+            PromptKind::GoTo => "go ",
+        }
+    }
+}
+
+/// 入力欄に打鍵を渡した結果
+#[derive(Debug, PartialEq)]
+pub enum PromptResult {
+    Editing,
+    /// Tab で補完を求められた
+    Complete,
+    Submit(String),
+    Cancel,
+}
+
+/// 画面下部の 1 行入力欄。キーはイベントとして受け取るので IME の入力もそのまま入る。
+pub struct Prompt {
+    pub kind: PromptKind,
+    pub input: String,
+    /// 補完やパレットの候補
+    pub candidates: Vec<String>,
+    /// パレットで選んでいる候補、または Tab で順に入れている候補
+    pub selected: usize,
+}
+
+impl Prompt {
+    pub fn new(kind: PromptKind) -> Prompt {
+        Prompt {
+            kind,
+            input: String::new(),
+            candidates: Vec::new(),
+            selected: 0,
+        }
+    }
+
+    /// パレットで選んでいる候補
+    pub fn selection(&self) -> Option<&str> {
+        self.candidates.get(self.selected).map(String::as_str)
+    }
+
+    pub fn on_key(&mut self, event: KeyEvent) -> PromptResult {
+        match event.code {
+            KeyCode::Tab => PromptResult::Complete,
+            KeyCode::Down => {
+                if self.selected + 1 < self.candidates.len() {
+                    self.selected += 1;
+                }
+                PromptResult::Editing
+            }
+            KeyCode::Up => {
+                self.selected = self.selected.saturating_sub(1);
+                PromptResult::Editing
+            }
+            KeyCode::Enter => PromptResult::Submit(self.input.clone()),
+            KeyCode::Esc => PromptResult::Cancel,
+            KeyCode::Char('c') if event.modifiers.contains(KeyModifiers::CONTROL) => {
+                PromptResult::Cancel
+            }
+            KeyCode::Char('u') if event.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.input.clear();
+                PromptResult::Editing
+            }
+            KeyCode::Backspace => {
+                self.input.pop();
+                PromptResult::Editing
+            }
+            KeyCode::Char(c) => {
+                self.input.push(c);
+                PromptResult::Editing
+            }
+            _ => PromptResult::Editing,
+        }
+    }
+}
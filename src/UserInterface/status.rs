@@ -0,0 +1,52 @@
+use std::time::{Duration, Instant};
+
+/// メッセージの重要度
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    /// 重要なものほど長く表示する
+    fn lifetime(self) -> Duration {
+        match self {
+            Severity::Info => Duration::from_secs(3),
+            Severity::Warning => Duration::from_secs(5),
+            Severity::Error => Duration::from_secs(8),
+        }
+    }
+}
+
+/// ステータスバーにしばらく表示する操作の結果
+#[derive(Clone, Debug)]
+pub struct Message {
+    pub severity: Severity,
+    pub text: String,
+    shown: Instant,
+}
+
+impl Message {
+    pub fn new(severity: Severity, text: String) -> Message {
+        Message {
+            severity,
+            text,
+            shown: Instant::now(),
+        }
+    }
+
+    /// 表示してからの経過時間（履歴用）
+    pub fn age(&self) -> String {
+        let seconds = self.shown.elapsed().as_secs();
+        match seconds {
+            0..=59 => format!("{}s", seconds),
+            60..=3599 => format!("{}m", seconds / 60),
+            _ => format!("{}h", seconds / 3600),
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.shown.elapsed() >= self.severity.lifetime()
+    }
+}
@@ -0,0 +1,112 @@
+use serde::Serialize;
+use std::fmt::{self, Display, Formatter};
+
+// 期限付きのタスク。今は組み込みの見本データだけを持つ。
+
+const TASKS: [&str; 24] = [
+    "Item1", "Item2", "Item3", "Item4", "Item5", "Item6", "Item7", "Item8", "Item9", "Item10",
+    "Item11", "Item12", "Item13", "Item14", "Item15", "Item16", "Item17", "Item18", "Item19",
+    "Item20", "Item21", "Item22", "Item23", "Item24",
+];
+
+const CLIENTS: [&str; 24] = [
+    "Clients1", "Clients2", "Clients3", "Clients4", "Clients5", "Clients6", "Clients7", "Clients8", "Clients9", "Clients10",
+    "Clients11", "Clients12", "Clients13", "Clients14", "Clients15", "Clients16", "Clients17", "Clients18", "Clients19",
+    "Clients20", "Clients21", "Clients22", "Clients23", "Clients24",
+];
+
+const DATES: [&str; 24] = [
+    "21-07-1", "21-07-2", "21-07-3", "21-07-4", "21-07-5", "21-07-6", "21-07-7", "21-07-8", "21-07-9", "21-07-10",
+    "21-07-11", "21-07-12", "21-07-13", "21-07-14", "21-07-15", "21-07-16", "21-07-17", "21-07-18", "21-07-19",
+    "21-07-20", "21-07-21", "21-07-22", "21-07-23", "21-07-24",
+];
+
+/// 期限日。`21-07-1` のような 2 桁の年も受け付ける。
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DueDate {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+}
+
+impl DueDate {
+    /// `YYYY-MM-DD` または `YY-MM-DD`
+    pub fn parse(text: &str) -> Result<DueDate, String> {
+        let invalid = || format!("`{}` は日付ではありません (YYYY-MM-DD)", text);
+        let parts: Vec<&str> = text.trim().split('-').collect();
+        let (year, month, day) = match parts.as_slice() {
+            [year, month, day] => (year, month, day),
+            _ => return Err(invalid()),
+        };
+        let year: u16 = year.parse().map_err(|_| invalid())?;
+        let month: u8 = month.parse().map_err(|_| invalid())?;
+        let day: u8 = day.parse().map_err(|_| invalid())?;
+        if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+            return Err(invalid());
+        }
+        let year = match year < 100 {
+            true => 2000 + year,
+            false => year,
+        };
+        Ok(DueDate { year, month, day })
+    }
+}
+
+impl Display for DueDate {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+impl Serialize for DueDate {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct DueTask {
+    pub name: String,
+    pub client: String,
+    pub due: DueDate,
+}
+
+/// 期限の早い順のタスク一覧
+pub fn all() -> Vec<DueTask> {
+    let mut tasks: Vec<DueTask> = TASKS
+        .iter()
+        .zip(CLIENTS.iter())
+        .zip(DATES.iter())
+        .filter_map(|((name, client), due)| {
+            Some(DueTask {
+                name: name.to_string(),
+                client: client.to_string(),
+                due: DueDate::parse(due).ok()?,
+            })
+        })
+        .collect();
+    tasks.sort_by_key(|task| task.due);
+    tasks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_short_and_long_years() {
+        let date = DueDate { year: 2021, month: 7, day: 1 };
+        assert_eq!(DueDate::parse("21-07-1"), Ok(date));
+        assert_eq!(DueDate::parse("2021-07-01"), Ok(date));
+        assert_eq!(date.to_string(), "2021-07-01");
+        assert!(DueDate::parse("2021-13-01").is_err());
+        assert!(DueDate::parse("tomorrow").is_err());
+    }
+
+    #[test]
+    fn tasks_are_ordered_by_due_date() {
+        let tasks = all();
+        assert_eq!(tasks.len(), 24);
+        assert!(tasks.windows(2).all(|pair| pair[0].due <= pair[1].due));
+    }
+}
@@ -0,0 +1,457 @@
+use serde::Deserialize;
+use std::convert::TryFrom;
+use std::env;
+use std::fs;
+use std::path::Path;
+use tui::style::{Color, Modifier, Style};
+
+/// 色名（`red`, `lightblue` など）、`#rrggbb`、または 0〜255 のパレット番号
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct ThemeColor(pub Color);
+
+impl TryFrom<String> for ThemeColor {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<ThemeColor, String> {
+        parse_color(&value)
+            .map(ThemeColor)
+            .ok_or_else(|| format!("invalid colour `{}`", value))
+    }
+}
+
+pub fn parse_color(value: &str) -> Option<Color> {
+    let color = match value.to_lowercase().as_str() {
+        "reset" => Color::Reset,
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "gray" | "grey" => Color::Gray,
+        "darkgray" | "darkgrey" => Color::DarkGray,
+        "lightred" => Color::LightRed,
+        "lightgreen" => Color::LightGreen,
+        "lightyellow" => Color::LightYellow,
+        "lightblue" => Color::LightBlue,
+        "lightmagenta" => Color::LightMagenta,
+        "lightcyan" => Color::LightCyan,
+        "white" => Color::White,
+        other => match other.strip_prefix('#') {
+            Some(hex) if hex.len() == 6 => {
+                let rgb = u32::from_str_radix(hex, 16).ok()?;
+                Color::Rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8)
+            }
+            Some(_) => return None,
+            None => Color::Indexed(other.parse().ok()?),
+        },
+    };
+    Some(color)
+}
+
+/// 組み込みのテーマ（名前と TOML）
+const BUNDLED: [(&str, &str); 3] = [
+    ("default", include_str!("../../themes/default.toml")),
+    ("light", include_str!("../../themes/light.toml")),
+    ("basic16", include_str!("../../themes/basic16.toml")),
+];
+
+/// 設定の `[theme]` とテーマファイルの中身。書いた色だけが元のテーマを上書きする。
+#[derive(Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThemeSpec {
+    /// 元にするテーマ。組み込みの名前か、`themes/<name>.toml`、または `.toml` のパス。
+    pub name: Option<String>,
+    /// `LS_COLORS` が設定されていればエントリの色に使う（既定は true）
+    pub ls_colors: Option<bool>,
+    pub border: Option<ThemeColor>,
+    pub title: Option<ThemeColor>,
+    pub highlight: Option<ThemeColor>,
+    pub tab: Option<ThemeColor>,
+    pub tab_highlight: Option<ThemeColor>,
+    pub status_fg: Option<ThemeColor>,
+    pub status_bg: Option<ThemeColor>,
+    pub info: Option<ThemeColor>,
+    pub warning: Option<ThemeColor>,
+    pub error: Option<ThemeColor>,
+    pub file: Option<ThemeColor>,
+    pub directory: Option<ThemeColor>,
+    pub executable: Option<ThemeColor>,
+    pub symlink: Option<ThemeColor>,
+    pub hidden: Option<ThemeColor>,
+}
+
+/// 一覧のエントリの種類（色分け用）
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EntryKind {
+    File,
+    Directory,
+    Executable,
+    Symlink,
+}
+
+#[derive(Clone)]
+pub struct Theme {
+    pub name: String,
+    pub border: Color,
+    pub title: Color,
+    pub highlight: Color,
+    pub tab: Color,
+    pub tab_highlight: Color,
+    pub status_fg: Color,
+    pub status_bg: Color,
+    pub info: Color,
+    pub warning: Color,
+    pub error: Color,
+    pub file: Color,
+    pub directory: Color,
+    pub executable: Color,
+    pub symlink: Color,
+    pub hidden: Color,
+    pub ls_colors: Option<LsColors>,
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        Theme::bundled("default").expect("the default theme is bundled")
+    }
+}
+
+impl Theme {
+    /// 何も塗らない土台。組み込みテーマはすべての色を書くので、ここの値は表に出ない。
+    fn plain() -> Theme {
+        Theme {
+            name: String::new(),
+            border: Color::Reset,
+            title: Color::Reset,
+            highlight: Color::Reset,
+            tab: Color::Reset,
+            tab_highlight: Color::Reset,
+            status_fg: Color::Reset,
+            status_bg: Color::Reset,
+            info: Color::Reset,
+            warning: Color::Reset,
+            error: Color::Reset,
+            file: Color::Reset,
+            directory: Color::Reset,
+            executable: Color::Reset,
+            symlink: Color::Reset,
+            hidden: Color::Reset,
+            ls_colors: LsColors::from_env(),
+        }
+    }
+
+    pub fn bundled_names() -> Vec<&'static str> {
+        BUNDLED.iter().map(|(name, _)| *name).collect()
+    }
+
+    /// 組み込みテーマ（`LS_COLORS` は環境変数から読む）
+    pub fn bundled(name: &str) -> Option<Theme> {
+        let (name, text) = BUNDLED.iter().find(|(n, _)| *n == name)?;
+        let spec: ThemeSpec = toml::from_str(text).expect("bundled themes are valid");
+        let mut theme = Theme::plain();
+        theme.name = name.to_string();
+        theme.apply(&spec);
+        Some(theme)
+    }
+
+    /// `[theme]` の指定からテーマを組み立てる。テーマファイルは `dir`（設定ファイルのある
+    /// ディレクトリ）から探す。
+    pub fn resolve(spec: &ThemeSpec, dir: Option<&Path>) -> Result<Theme, String> {
+        let name = spec.name.as_deref().unwrap_or("default");
+        let mut theme = match Theme::bundled(name) {
+            Some(theme) => theme,
+            None => {
+                let path = match name.ends_with(".toml") {
+                    true => dir.map(|dir| dir.join(name)).unwrap_or_else(|| name.into()),
+                    false => match dir {
+                        Some(dir) => dir.join("themes").join(format!("{}.toml", name)),
+                        None => {
+                            return Err(format!(
+                                "[theme] unknown theme `{}` (bundled: {})",
+                                name,
+                                Theme::bundled_names().join(", ")
+                            ))
+                        }
+                    },
+                };
+                let text = fs::read_to_string(&path).map_err(|e| {
+                    format!(
+                        "[theme] unknown theme `{}` (bundled: {}; or {}: {})",
+                        name,
+                        Theme::bundled_names().join(", "),
+                        path.display(),
+                        e
+                    )
+                })?;
+                let file: ThemeSpec = toml::from_str(&text)
+                    .map_err(|e| format!("[theme] {}: {}", path.display(), e))?;
+                if file.name.is_some() {
+                    return Err(format!("[theme] {}: a theme file cannot set name", path.display()));
+                }
+                let mut theme = Theme {
+                    name: name.to_string(),
+                    ..Theme::default()
+                };
+                theme.apply(&file);
+                theme
+            }
+        };
+        theme.apply(spec);
+        Ok(theme)
+    }
+
+    fn apply(&mut self, spec: &ThemeSpec) {
+        let colors = [
+            (&mut self.border, spec.border),
+            (&mut self.title, spec.title),
+            (&mut self.highlight, spec.highlight),
+            (&mut self.tab, spec.tab),
+            (&mut self.tab_highlight, spec.tab_highlight),
+            (&mut self.status_fg, spec.status_fg),
+            (&mut self.status_bg, spec.status_bg),
+            (&mut self.info, spec.info),
+            (&mut self.warning, spec.warning),
+            (&mut self.error, spec.error),
+            (&mut self.file, spec.file),
+            (&mut self.directory, spec.directory),
+            (&mut self.executable, spec.executable),
+            (&mut self.symlink, spec.symlink),
+            (&mut self.hidden, spec.hidden),
+        ];
+        for (color, value) in colors {
+            if let Some(value) = value {
+                *color = value.0;
+            }
+        }
+        if spec.ls_colors == Some(false) {
+            self.ls_colors = None;
+        }
+    }
+
+    /// 一覧のエントリの色。`LS_COLORS` に該当があればそれを、無ければテーマの色を使う。
+    pub fn entry_style(&self, kind: EntryKind, name: &str, hidden: bool) -> Style {
+        if let Some(style) = self.ls_colors.as_ref().and_then(|ls| ls.style(kind, name)) {
+            return style;
+        }
+        Style::default().fg(self.entry_color(kind, hidden))
+    }
+
+    /// テーマだけで決まるエントリの色
+    pub fn entry_color(&self, kind: EntryKind, hidden: bool) -> Color {
+        match (kind, hidden) {
+            (_, true) => self.hidden,
+            (EntryKind::Directory, _) => self.directory,
+            (EntryKind::Executable, _) => self.executable,
+            (EntryKind::Symlink, _) => self.symlink,
+            (EntryKind::File, _) => self.file,
+        }
+    }
+
+    pub fn status_style(&self) -> Style {
+        Style::default().fg(self.status_fg).bg(self.status_bg)
+    }
+}
+
+/// `LS_COLORS` の種類別（`di`, `ln`, `ex`, `fi`）と拡張子別（`*.rs`）の色
+#[derive(Clone, Default)]
+pub struct LsColors {
+    kinds: Vec<(String, Style)>,
+    extensions: Vec<(String, Style)>,
+}
+
+impl LsColors {
+    pub fn from_env() -> Option<LsColors> {
+        env::var("LS_COLORS")
+            .ok()
+            .filter(|value| !value.is_empty())
+            .map(|value| LsColors::parse(&value))
+    }
+
+    pub fn parse(value: &str) -> LsColors {
+        let mut colors = LsColors::default();
+        for item in value.split(':') {
+            let (key, codes) = match item.split_once('=') {
+                Some(pair) => pair,
+                None => continue,
+            };
+            let style = sgr_style(codes);
+            match key.strip_prefix('*') {
+                Some(suffix) => colors.extensions.push((suffix.to_lowercase(), style)),
+                None => colors.kinds.push((key.to_string(), style)),
+            }
+        }
+        colors
+    }
+
+    fn kind(&self, key: &str) -> Option<Style> {
+        self.kinds.iter().find(|(k, _)| k == key).map(|(_, style)| *style)
+    }
+
+    /// GNU ls と同じく、ディレクトリ・リンク・実行ファイルは種類の色を、
+    /// 通常のファイルは拡張子の色を優先する
+    pub fn style(&self, kind: EntryKind, name: &str) -> Option<Style> {
+        match kind {
+            EntryKind::Directory => return self.kind("di"),
+            EntryKind::Symlink => return self.kind("ln"),
+            EntryKind::Executable => {
+                if let Some(style) = self.kind("ex") {
+                    return Some(style);
+                }
+            }
+            EntryKind::File => {}
+        }
+        let name = name.to_lowercase();
+        self.extensions
+            .iter()
+            .rev()
+            .find(|(suffix, _)| name.ends_with(suffix.as_str()))
+            .map(|(_, style)| *style)
+            .or_else(|| self.kind("fi"))
+    }
+}
+
+/// `01;38;5;208` のような SGR の並びを Style にする。
+/// `38;5;N` などの引数は位置で決まるので、空の欄は 0 とみなし、読めない欄があればそこで打ち切る。
+fn sgr_style(codes: &str) -> Style {
+    let codes: Vec<u16> = codes
+        .split(';')
+        .map_while(|code| match code {
+            "" => Some(0),
+            _ => code.parse().ok(),
+        })
+        .collect();
+    let mut style = Style::default();
+    let mut i = 0;
+    while i < codes.len() {
+        match codes[i] {
+            0 => style = Style::default(),
+            1 => style = style.add_modifier(Modifier::BOLD),
+            2 => style = style.add_modifier(Modifier::DIM),
+            3 => style = style.add_modifier(Modifier::ITALIC),
+            4 => style = style.add_modifier(Modifier::UNDERLINED),
+            5 => style = style.add_modifier(Modifier::SLOW_BLINK),
+            7 => style = style.add_modifier(Modifier::REVERSED),
+            code @ 30..=37 => style = style.fg(ansi_color(code - 30)),
+            code @ 40..=47 => style = style.bg(ansi_color(code - 40)),
+            code @ 90..=97 => style = style.fg(ansi_color(code - 90 + 8)),
+            code @ 100..=107 => style = style.bg(ansi_color(code - 100 + 8)),
+            code @ 38 | code @ 48 => {
+                let color = match codes.get(i + 1) {
+                    Some(5) => codes.get(i + 2).map(|n| {
+                        i += 2;
+                        Color::Indexed(*n as u8)
+                    }),
+                    Some(2) if codes.len() > i + 4 => {
+                        let color =
+                            Color::Rgb(codes[i + 2] as u8, codes[i + 3] as u8, codes[i + 4] as u8);
+                        i += 4;
+                        Some(color)
+                    }
+                    _ => None,
+                };
+                if let Some(color) = color {
+                    style = match code {
+                        38 => style.fg(color),
+                        _ => style.bg(color),
+                    };
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    style
+}
+
+fn ansi_color(index: u16) -> Color {
+    match index {
+        0 => Color::Black,
+        1 => Color::Red,
+        2 => Color::Green,
+        3 => Color::Yellow,
+        4 => Color::Blue,
+        5 => Color::Magenta,
+        6 => Color::Cyan,
+        7 => Color::Gray,
+        8 => Color::DarkGray,
+        9 => Color::LightRed,
+        10 => Color::LightGreen,
+        11 => Color::LightYellow,
+        12 => Color::LightBlue,
+        13 => Color::LightMagenta,
+        14 => Color::LightCyan,
+        _ => Color::White,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_kinds_and_extensions() {
+        let colors = LsColors::parse("di=01;34:*.rs=38;5;208");
+        assert_eq!(
+            colors.style(EntryKind::Directory, "src"),
+            Some(Style::default().add_modifier(Modifier::BOLD).fg(Color::Blue))
+        );
+        assert_eq!(
+            colors.style(EntryKind::File, "MAIN.RS"),
+            Some(Style::default().fg(Color::Indexed(208)))
+        );
+        assert_eq!(colors.style(EntryKind::File, "notes.txt"), None);
+    }
+
+    #[test]
+    fn reads_sgr_operands_by_position() {
+        assert_eq!(sgr_style("38;2;;128;255"), Style::default().fg(Color::Rgb(0, 128, 255)));
+        assert_eq!(sgr_style("48;5;"), Style::default().bg(Color::Indexed(0)));
+        // 読めない欄より後ろは使わない
+        assert_eq!(sgr_style("01;x;38;5;208"), Style::default().add_modifier(Modifier::BOLD));
+    }
+
+    #[test]
+    fn executables_prefer_ex_over_extensions() {
+        let colors = LsColors::parse("ex=01;32:*.sh=33:fi=37");
+        let executable = Style::default().add_modifier(Modifier::BOLD).fg(Color::Green);
+        assert_eq!(colors.style(EntryKind::Executable, "run.sh"), Some(executable));
+        assert_eq!(
+            colors.style(EntryKind::File, "run.sh"),
+            Some(Style::default().fg(Color::Yellow))
+        );
+        assert_eq!(colors.style(EntryKind::File, "run"), Some(Style::default().fg(Color::Gray)));
+        // ex が無ければ実行ファイルも拡張子で塗る
+        let colors = LsColors::parse("*.sh=33");
+        assert_eq!(
+            colors.style(EntryKind::Executable, "run.sh"),
+            Some(Style::default().fg(Color::Yellow))
+        );
+    }
+
+    #[test]
+    fn theme_keys_override_a_bundled_theme() {
+        let spec: ThemeSpec = toml::from_str(
+            r##"
+            name = "light"
+            highlight = "#ff8800"
+            ls_colors = false
+            "##,
+        )
+        .unwrap();
+        let theme = Theme::resolve(&spec, None).unwrap();
+        let light = Theme::bundled("light").unwrap();
+        assert_eq!(theme.name, "light");
+        assert_eq!(theme.highlight, Color::Rgb(0xff, 0x88, 0x00));
+        assert_eq!(theme.border, light.border);
+        assert_eq!(theme.directory, light.directory);
+        assert!(theme.ls_colors.is_none());
+        let unknown = ThemeSpec {
+            name: Some(String::from("nothing")),
+            ..ThemeSpec::default()
+        };
+        assert!(Theme::resolve(&unknown, None).is_err());
+    }
+}
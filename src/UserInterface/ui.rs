@@ -1,3 +1,12 @@
+use crate::UserInterface::disk::human_size;
+use crate::config::Split;
+use crate::UserInterface::keymap::Action;
+use crate::UserInterface::mouse::HitMap;
+use crate::UserInterface::names;
+use crate::UserInterface::pane::DirTab;
+use crate::UserInterface::prompt::PromptKind;
+use crate::UserInterface::status::Severity;
+use crate::UserInterface::theme::{EntryKind, Theme};
 use crate::UserInterface::App;
 use tui::{
     backend::Backend,
@@ -7,111 +16,385 @@ use tui::{
     text::{Span, Spans},
     widgets::canvas::{Canvas, Line, Map, MapResolution, Rectangle},
     widgets::{
-        Axis, BarChart, Block, Borders, Cell, Chart, Dataset, Gauge, LineGauge, List, ListItem,
-        Paragraph, Row, Sparkline, Table, Tabs, Wrap,
+        BarChart, Block, Borders, Cell, Clear, Gauge, List, ListItem, ListState, Paragraph, Row,
+        Sparkline, Table, Tabs,
     },
     Frame,
 };
+use unicode_width::UnicodeWidthStr;
+
+/// これより狭いとペインを縦に積む
+const STACK_WIDTH: u16 = 80;
+/// これより狭いとディスク情報の欄を出さない
+const SIDE_PANEL_WIDTH: u16 = 100;
+/// これより低いとタブを枠なしの 1 行にし、ヘルプの案内と補完候補を省く
+const COMPACT_HEIGHT: u16 = 16;
+/// ペインの枠に並べるタブの見出しの最大幅
+const PANE_TAB_WIDTH: usize = 16;
+/// パンくずの区切り文字
+const SEPARATOR: &str = std::path::MAIN_SEPARATOR_STR;
+
+/// 端末の大きさに合わせた配置。描画のたびに決め直すので、大きさが変われば次の描画で切り替わる。
+#[derive(Clone, Copy)]
+struct Responsive {
+    stacked: bool,
+    side_panel: bool,
+    compact: bool,
+}
+
+impl Responsive {
+    fn new(area: Rect) -> Responsive {
+        Responsive {
+            stacked: area.width < STACK_WIDTH,
+            side_panel: area.width >= SIDE_PANEL_WIDTH,
+            compact: area.height < COMPACT_HEIGHT,
+        }
+    }
+
+    /// 重ねて出す窓の幅（%）。狭い端末では全幅を使う。
+    fn overlay_percent(self, percent: u16) -> u16 {
+        match self.stacked {
+            true => 100,
+            false => percent,
+        }
+    }
+}
 
 pub fn draw<B: Backend>(f: &mut Frame<B>, app: &mut App) {
+    let layout = Responsive::new(f.size());
+    let tabs_height = match layout.compact {
+        true => 1,
+        false => 3,
+    };
     let chunks = Layout::default()
-        .constraints([Constraint::Length(3), Constraint::Min(0)].as_ref())
+        .constraints([Constraint::Length(tabs_height), Constraint::Min(0), Constraint::Length(1)].as_ref())
         .split(f.size());
-    let titles = app
+    let titles: Vec<Spans> = app
         .tabs
         .titles
         .iter()
-        .map(|t| Spans::from(Span::styled(*t, Style::default().fg(Color::Green))))
+        .map(|t| Spans::from(Span::styled(*t, Style::default().fg(app.config.theme.tab))))
         .collect();
+    let block = match layout.compact {
+        true => Block::default(),
+        false => Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(app.config.theme.border))
+            .title(tabs_title(app)),
+    };
+    app.hits = HitMap {
+        tabs: tab_areas(&titles, block.inner(chunks[0])),
+        ..HitMap::default()
+    };
     let tabs = Tabs::new(titles)
-        .block(Block::default().borders(Borders::ALL).title(app.title))
-        .highlight_style(Style::default().fg(Color::Yellow))
+        .block(block)
+        .highlight_style(Style::default().fg(app.config.theme.tab_highlight))
         .select(app.tabs.index);
     f.render_widget(tabs, chunks[0]);
     match app.tabs.index {
-        0 => draw_first_tab(f, app, chunks[1]),
+        0 => draw_first_tab(f, app, chunks[1], layout),
         1 => draw_second_tab(f, app, chunks[1]),
         2 => draw_third_tab(f, app, chunks[1]),
         _ => {}
     };
+    draw_status_bar(f, app, chunks[2], layout);
+    if app.show_help {
+        draw_help(f, app, f.size(), layout);
+    }
+    if app.show_log {
+        draw_log(f, app, f.size(), layout);
+    }
+}
+
+/// タブの見出しを置く場所（`Tabs` と同じく、`area` の中に 1 桁ずつ空けて区切り文字を挟む）
+fn tab_areas(titles: &[Spans], area: Rect) -> Vec<(Rect, usize)> {
+    let mut areas = Vec::new();
+    let mut x = area.x;
+    let right = area.right();
+    for (i, title) in titles.iter().enumerate() {
+        x += 1;
+        if x >= right {
+            break;
+        }
+        let width = (title.width() as u16).min(right - x);
+        areas.push((Rect::new(x, area.y, width, 1), i));
+        x += width + 2;
+    }
+    areas
 }
 
-fn draw_first_tab<B>(f: &mut Frame<B>, app: &mut App, area: Rect)
+/// フォーカス中のペインのパス、カーソル位置と件数、印の数とサイズ、操作の結果。
+/// 収まらないパスは真ん中を省く。
+fn draw_status_bar<B>(f: &mut Frame<B>, app: &App, area: Rect, layout: Responsive)
 where
     B: Backend,
 {
-    let chunks = Layout::default()
-        .constraints(
-            [
-                Constraint::Percentage(80),
-                Constraint::Percentage(20),
-            ]
-            .as_ref(),
+    let theme = &app.config.theme;
+    let list = &app.focused().list;
+    let path = app
+        .pane_dir(app.folders_index)
+        .map(|dir| names::escape_path(&dir))
+        .unwrap_or_default();
+    let position = match list.state.selected() {
+        Some(i) => format!("{}/{}", i + 1, list.items.len()),
+        None => format!("-/{}", list.items.len()),
+    };
+    let total: u64 = list.items.iter().filter(|task| !task.is_dir).map(|task| task.size).sum();
+    let (marked, marked_size) = app.marked_summary();
+    let mut info = format!(" {}  {}", position, human_size(total));
+    if marked > 0 {
+        info.push_str(&format!("  印 {} ({})", marked, human_size(marked_size)));
+    }
+    // 低い端末ではタブの枠がないので、入力途中の複数打鍵をここに出す
+    let pending = app.keymap.pending();
+    if layout.compact && !pending.is_empty() {
+        info.insert_str(0, &format!(" {}…", pending));
+    }
+    info.push(' ');
+    let info = Span::styled(info, theme.status_style());
+
+    let message = app.message.as_ref().map(|message| {
+        let color = match message.severity {
+            Severity::Info => theme.info,
+            Severity::Warning => theme.warning,
+            Severity::Error => theme.error,
+        };
+        Span::styled(
+            format!(" {} ", message.text),
+            Style::default().fg(theme.status_fg).bg(color),
         )
-        .split(area);
-    draw_charts(f, app, chunks[0]);
-    draw_text(f, chunks[1]);
+    });
+    let others = info.width() + message.as_ref().map_or(0, |message| message.width());
+    let path = names::middle_ellipsis(&path, (area.width as usize).saturating_sub(others + 2));
+    let mut spans = vec![Span::styled(format!(" {} ", path), theme.status_style().add_modifier(Modifier::BOLD))];
+    spans.extend(message);
+    // 右端に件数などを寄せる
+    let used: usize = spans.iter().map(|span| span.width()).sum();
+    let fill = (area.width as usize).saturating_sub(used + info.width());
+    spans.push(Span::styled(" ".repeat(fill), theme.status_style()));
+    spans.push(info);
+    f.render_widget(Paragraph::new(Spans::from(spans)), area);
+}
+
+/// タイトルにヘルプのキーと、入力途中の複数打鍵を添える
+fn tabs_title(app: &App) -> String {
+    let mut title = String::from(app.title);
+    if let Some(key) = app.keymap.keys_for(Action::Help).first() {
+        title.push_str(&format!(" ({}: ヘルプ)", key));
+    }
+    let pending = app.keymap.pending();
+    if !pending.is_empty() {
+        title.push_str(&format!(" {}…", pending));
+    }
+    title
 }
 
-fn draw_gauges<B>(f: &mut Frame<B>, app: &mut App, area: Rect)
+fn draw_first_tab<B>(f: &mut Frame<B>, app: &mut App, area: Rect, layout: Responsive)
 where
     B: Backend,
 {
+    let prompt_height = match &app.prompt {
+        Some(prompt) if is_overlay(prompt.kind) => 0,
+        Some(prompt) if !prompt.candidates.is_empty() && !layout.compact => 4,
+        Some(_) => 3,
+        None => 0,
+    };
     let chunks = Layout::default()
-        .constraints(
-            [
-                Constraint::Length(2),
-                Constraint::Length(3),
-                Constraint::Length(1),
-            ]
-            .as_ref(),
+        .constraints([Constraint::Min(0), Constraint::Length(prompt_height)].as_ref())
+        .split(area);
+    match app.du {
+        Some(_) => draw_du(f, app, chunks[0]),
+        None => draw_charts(f, app, chunks[0], layout),
+    }
+    match &app.prompt {
+        Some(prompt) if is_overlay(prompt.kind) => draw_palette(f, app, area, layout),
+        Some(_) => draw_prompt(f, app, chunks[1]),
+        None => {}
+    }
+}
+
+/// 候補を入力欄の下に重ねて出す入力欄
+fn is_overlay(kind: PromptKind) -> bool {
+    matches!(kind, PromptKind::Palette | PromptKind::Jump)
+}
+
+fn draw_du<B>(f: &mut Frame<B>, app: &mut App, area: Rect)
+where
+    B: Backend,
+{
+    let du = match app.du.as_mut() {
+        Some(du) => du,
+        None => return,
+    };
+    let tree = match du.tree.lock() {
+        Ok(tree) => tree,
+        Err(_) => return,
+    };
+    let theme = &app.config.theme;
+    let current = &tree.nodes[du.current];
+    let children = tree.sorted_children(du.current);
+    let bar_width = 20;
+    let items: Vec<ListItem> = children
+        .iter()
+        .map(|id| {
+            let node = &tree.nodes[*id];
+            let ratio = match current.size {
+                0 => 0.0,
+                total => node.size as f64 / total as f64,
+            };
+            let filled = (ratio * bar_width as f64).round() as usize;
+            let (name, style) = match node.is_dir {
+                true => (
+                    format!("{}/", names::escape(&node.name)),
+                    theme
+                        .entry_style(EntryKind::Directory, &node.name.to_string_lossy(), false)
+                        .add_modifier(Modifier::BOLD),
+                ),
+                false => (
+                    names::escape(&node.name),
+                    theme.entry_style(EntryKind::File, &node.name.to_string_lossy(), false),
+                ),
+            };
+            ListItem::new(Spans::from(vec![
+                Span::raw(format!("{:>9} ", human_size(node.size))),
+                Span::styled(
+                    format!("[{:<width$}]", "#".repeat(filled), width = bar_width),
+                    Style::default().fg(theme.highlight),
+                ),
+                Span::raw(format!(" {:>5.1}% ", ratio * 100.0)),
+                Span::styled(name, style),
+            ]))
+        })
+        .collect();
+
+    let status = match (du.pending_delete, du.is_done()) {
+        (Some(id), _) => format!(
+            "{} を削除しますか？ ({} で削除、ほかのキーで取り消し)",
+            names::escape(&tree.nodes[id].name),
+            app.keymap.keys_for(Action::Confirm).join(", ")
+        ),
+        (None, true) => String::from("完了"),
+        (None, false) => format!(
+            "スキャン中… {} 件",
+            du.scanned.load(std::sync::atomic::Ordering::Relaxed)
+        ),
+    };
+    let title = format!(
+        "Usage: {} {} ({} 件) [{}]",
+        tree.path(&du.root, du.current).display(),
+        human_size(current.size),
+        current.count,
+        status
+    );
+    du.state
+        .select(du.selected.and_then(|id| children.iter().position(|child| *child == id)));
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(theme.border))
+                .title(title),
         )
+        .highlight_style(Style::default().fg(theme.highlight))
+        .highlight_symbol("> ");
+    f.render_stateful_widget(list, area, &mut du.state);
+}
+
+fn draw_disk_info<B>(f: &mut Frame<B>, app: &mut App, area: Rect)
+where
+    B: Backend,
+{
+    app.hits.disk_info = Some(area);
+    // ペインごとのゲージ、転送量、大きいエントリの順に並べる
+    let panes = app.panes.len();
+    let mut constraints = vec![Constraint::Length(2); panes];
+    constraints.extend([Constraint::Length(4), Constraint::Min(0)]);
+    let chunks = Layout::default()
+        .constraints(constraints)
         .margin(1)
         .split(area);
-    let block = Block::default().borders(Borders::ALL).title("Graphs");
+    let block = Block::default().borders(Borders::ALL).title("Disk");
     f.render_widget(block, area);
 
-    let label = format!("{:.2}%", app.progress * 100.0);
-    let gauge = Gauge::default()
-        .block(Block::default().title("Gauge:"))
-        .gauge_style(
-            Style::default()
-                .fg(Color::Magenta)
-                .bg(Color::Black)
-                .add_modifier(Modifier::ITALIC | Modifier::BOLD),
-        )
-        .label(label)
-        .ratio(app.progress);
-    f.render_widget(gauge, chunks[0]);
+    for (index, disk) in app.panes.iter().map(|pane| &pane.disk).enumerate() {
+        let (label, ratio) = match disk {
+            Some(disk) => (
+                format!(
+                    "{} / {} ({} free)",
+                    human_size(disk.used()),
+                    human_size(disk.total),
+                    human_size(disk.available)
+                ),
+                disk.ratio(),
+            ),
+            None => (String::from("-"), 0.0),
+        };
+        let style = if index == app.folders_index {
+            Style::default().fg(Color::Magenta).add_modifier(Modifier::BOLD)
+        } else {
+            Style::default().fg(Color::Magenta)
+        };
+        let gauge = Gauge::default()
+            .block(Block::default().title(format!("Pane{}:", index + 1)))
+            .gauge_style(style.bg(Color::Black))
+            .label(label)
+            .ratio(ratio.min(1.0));
+        f.render_widget(gauge, chunks[index]);
+    }
 
     let sparkline = Sparkline::default()
-        .block(Block::default().title("Sparkline:"))
+        .block(Block::default().title(format!(
+            "Jobs: {}/tick",
+            human_size(app.throughput.last())
+        )))
         .style(Style::default().fg(Color::Green))
-        .data(&app.sparkline.points)
+        .data(&app.throughput.points)
         .bar_set(if app.enhanced_graphics {
             symbols::bar::NINE_LEVELS
         } else {
             symbols::bar::THREE_LEVELS
         });
-    f.render_widget(sparkline, chunks[1]);
+    f.render_widget(sparkline, chunks[panes]);
 
-    let line_gauge = LineGauge::default()
-        .block(Block::default().title("LineGauge:"))
-        .gauge_style(Style::default().fg(Color::Magenta))
-        .line_set(if app.enhanced_graphics {
-            symbols::line::THICK
-        } else {
-            symbols::line::NORMAL
-        })
-        .ratio(app.progress);
-    f.render_widget(line_gauge, chunks[2]);
+    // 1 MiB に満たない子も見えるよう、いちばん大きい子に対する百分率で描く
+    let top = app
+        .largest
+        .as_ref()
+        .and_then(|largest| largest.items.iter().map(|(_, size)| *size).max())
+        .unwrap_or(0);
+    let data: Vec<(&str, u64)> = match &app.largest {
+        Some(largest) => largest
+            .items
+            .iter()
+            .map(|(name, size)| (name.as_str(), percent_of(*size, top)))
+            .collect(),
+        None => Vec::new(),
+    };
+    let barchart = BarChart::default()
+        .block(Block::default().title(format!("Largest (% of {}):", human_size(top))))
+        .data(&data)
+        .bar_width(6)
+        .bar_gap(1)
+        .bar_style(Style::default().fg(Color::Yellow))
+        .value_style(Style::default().fg(Color::Black).bg(Color::Yellow));
+    f.render_widget(barchart, chunks[panes + 1]);
+}
+
+/// `top` に対する `size` の百分率。0 でなければ少なくとも 1 にする。
+fn percent_of(size: u64, top: u64) -> u64 {
+    match top {
+        0 => 0,
+        _ => (size as u128 * 100).div_ceil(top as u128) as u64,
+    }
 }
 
-fn draw_charts<B>(f: &mut Frame<B>, app: &mut App, area: Rect)
+fn draw_charts<B>(f: &mut Frame<B>, app: &mut App, area: Rect, layout: Responsive)
 where
     B: Backend,
 {
-    let constraints = if app.show_chart {
+    // 狭い端末ではディスク情報を出さず、ペインに幅を譲る
+    let side_panel = app.show_chart && layout.side_panel;
+    let constraints = if side_panel {
         vec![Constraint::Percentage(50), Constraint::Percentage(50)]
     } else {
         vec![Constraint::Percentage(100)]
@@ -121,37 +404,20 @@ where
         .direction(Direction::Horizontal)
         .split(area);
     {
+        let direction = match (app.config.view.split, layout.stacked) {
+            (Split::Columns, _) | (Split::Auto, false) => Direction::Horizontal,
+            (Split::Rows, _) | (Split::Auto, true) => Direction::Vertical,
+        };
+        let panes = app.panes.len();
         let chunks = Layout::default()
-            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
-            .direction(Direction::Horizontal)
+            .constraints(vec![Constraint::Ratio(1, panes as u32); panes])
+            .direction(direction)
             .split(chunks[0]);
 
         // Draw tasks
-        let tasks: Vec<ListItem> = app
-            .folders[0]
-            .items
-            .iter()
-            .map(|i| ListItem::new(vec![Spans::from(Span::raw(format!("{}", i)))]))
-            .collect();
-        let tasks = List::new(tasks)
-            .block(Block::default().borders(Borders::ALL).title("Task"))
-            //.highlight_style(Style::default().add_modifier(Modifier::BOLD))
-            .highlight_style(Style::default().fg(Color::Red))
-            .highlight_symbol("> ");
-        f.render_stateful_widget(tasks, chunks[0], &mut app.folders[0].state);
-
-        let tasks: Vec<ListItem> = app
-            .folders[1]
-            .items
-            .iter()
-            .map(|i| ListItem::new(vec![Spans::from(Span::raw(format!("{}", i)))]))
-            .collect();
-        let tasks = List::new(tasks)
-            .block(Block::default().borders(Borders::ALL).title("Task"))
-            //.highlight_style(Style::default().add_modifier(Modifier::BOLD))
-            .highlight_style(Style::default().fg(Color::Red))
-            .highlight_symbol("> ");
-        f.render_stateful_widget(tasks, chunks[1], &mut app.folders[1].state);
+        for (index, chunk) in chunks.into_iter().enumerate() {
+            draw_pane(f, app, index, chunk);
+        }
 
         // Draw logs
         //let clients: Vec<ListItem> = app
@@ -181,108 +447,355 @@ where
             //.highlight_symbol("> ");
         //f.render_stateful_widget(dates, chunks[2], &mut app.tasks.state);
     }
-    if app.show_chart {
-        let x_labels = vec![
-            Span::styled(
-                format!("{}", app.signals.window[0]),
-                Style::default().add_modifier(Modifier::BOLD),
-            ),
-            Span::raw(format!(
-                "{}",
-                (app.signals.window[0] + app.signals.window[1]) / 2.0
-            )),
-            Span::styled(
-                format!("{}", app.signals.window[1]),
-                Style::default().add_modifier(Modifier::BOLD),
-            ),
-        ];
-        let datasets = vec![
-            Dataset::default()
-                .name("data2")
-                .marker(symbols::Marker::Dot)
-                .style(Style::default().fg(Color::Cyan))
-                .data(&app.signals.sin1.points),
-            Dataset::default()
-                .name("data3")
-                .marker(if app.enhanced_graphics {
-                    symbols::Marker::Braille
-                } else {
-                    symbols::Marker::Dot
-                })
-                .style(Style::default().fg(Color::Yellow))
-                .data(&app.signals.sin2.points),
-        ];
-        let chart = Chart::new(datasets)
-            .block(
-                Block::default()
-                    .title(Span::styled(
-                        "Chart",
-                        Style::default()
-                            .fg(Color::Cyan)
-                            .add_modifier(Modifier::BOLD),
-                    ))
-                    .borders(Borders::ALL),
-            )
-            .x_axis(
-                Axis::default()
-                    .title("X Axis")
-                    .style(Style::default().fg(Color::Gray))
-                    .bounds(app.signals.window)
-                    .labels(x_labels),
-            )
-            .y_axis(
-                Axis::default()
-                    .title("Y Axis")
-                    .style(Style::default().fg(Color::Gray))
-                    .bounds([-20.0, 20.0])
-                    .labels(vec![
-                        Span::styled("-20", Style::default().add_modifier(Modifier::BOLD)),
-                        Span::raw("0"),
-                        Span::styled("20", Style::default().add_modifier(Modifier::BOLD)),
-                    ]),
-            );
-        f.render_widget(chart, chunks[1]);
+    if side_panel {
+        draw_disk_info(f, app, chunks[1]);
+    }
+}
+
+/// ペインの一覧を描画する。見えている行だけを整形するので、ディレクトリが大きくても描画の手間は変わらない。
+/// 幅に収まらない名前は真ん中を省く。
+fn draw_pane<B>(f: &mut Frame<B>, app: &mut App, index: usize, area: Rect)
+where
+    B: Backend,
+{
+    app.hits.panes.push((area, index));
+    let title = pane_title(app, index, area);
+    let theme = &app.config.theme;
+    let mode = app.config.view.display;
+    let root = &app.root;
+    let DirTab { list, marks, .. } = app.panes[index].current_mut();
+    let range = list.window(area.height.saturating_sub(2) as usize);
+    // 枠、選択記号 `> `、印の分を除いた幅
+    let name_width = area.width.saturating_sub(5) as usize;
+    for i in range.clone() {
+        list.row(i, |task| task.display_name(mode, root));
+    }
+    let offset = range.start;
+    let tasks: Vec<ListItem> = range
+        .map(|i| {
+            let task = &list.items[i];
+            let style = theme.entry_style(task.kind(), &task.name(), task.hidden);
+            let mark = match marks.contains(&task.path) {
+                true => Span::styled("*", Style::default().fg(theme.highlight).add_modifier(Modifier::BOLD)),
+                false => Span::raw(" "),
+            };
+            let name = names::middle_ellipsis(list.cached(i), name_width);
+            ListItem::new(vec![Spans::from(vec![mark, Span::raw(name)])]).style(style)
+        })
+        .collect();
+    let mut state = ListState::default();
+    state.select(list.state.selected().map(|selected| selected - offset));
+    let tasks = List::new(tasks)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(theme.border))
+                .title(title),
+        )
+        .highlight_style(
+            Style::default()
+                .fg(theme.highlight)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol("> ");
+    f.render_stateful_widget(tasks, area, &mut state);
+    draw_pane_tabs(f, app, index, area);
+}
+
+/// ペインの枠のタイトル。今のディレクトリをパンくずで表し、区切りごとにクリックできるよう位置を覚えておく。
+/// 収まらなければ根の側から `…` に置き換える。
+fn pane_title(app: &mut App, index: usize, area: Rect) -> Spans<'static> {
+    let suffix = app
+        .pane_loading(index)
+        .map(|loaded| format!(" (loading… {})", loaded))
+        .unwrap_or_default();
+    let pane = &app.panes[index];
+    // ドライブの一覧や、空のパスのように区切りが 1 つも無いときはタブの名前を出す
+    let crumbs = match app.pane_dir(index).map(|dir| names::breadcrumb(&dir)) {
+        Some(crumbs) if !crumbs.is_empty() => crumbs,
+        _ => return Spans::from(format!("{}{}", pane.tabs.titles[pane.tabs.index], suffix)),
+    };
+    // 根のように区切り文字で終わる区切りの後には区切り文字を足さない
+    let parts: Vec<(&str, &str)> = crumbs
+        .iter()
+        .enumerate()
+        .map(|(i, crumb)| match i > 0 && !crumbs[i - 1].label.ends_with(['/', '\\']) {
+            true => (SEPARATOR, crumb.label.as_str()),
+            false => ("", crumb.label.as_str()),
+        })
+        .collect();
+    let width = (area.width as usize).saturating_sub(2 + suffix.width());
+    let needed = |from: usize| {
+        let ellipsis = match from > 0 {
+            true => 1,
+            false => 0,
+        };
+        ellipsis + parts[from..].iter().map(|(sep, label)| sep.width() + label.width()).sum::<usize>()
+    };
+    let last = parts.len() - 1;
+    let first = (0..parts.len()).find(|&from| needed(from) <= width).unwrap_or(last);
+
+    let theme = &app.config.theme;
+    let selected = match index == app.folders_index {
+        true => app.crumb,
+        false => None,
+    };
+    let mut spans = Vec::new();
+    // 枠の角の次から書かれる
+    let mut x = area.x + 1;
+    if first > 0 {
+        spans.push(Span::raw("…"));
+        x += 1;
+    }
+    for (i, (sep, label)) in parts.iter().enumerate().skip(first) {
+        let label = match i == first && first == last {
+            true => names::middle_ellipsis(label, width.saturating_sub(needed(last) - label.width())),
+            false => label.to_string(),
+        };
+        spans.push(Span::raw(*sep));
+        x += sep.width() as u16;
+        let label_width = label.width() as u16;
+        app.hits.crumbs.push((Rect::new(x, area.y, label_width, 1), index, i));
+        x += label_width;
+        let style = match (Some(i) == selected, i == last) {
+            (true, _) => Style::default().add_modifier(Modifier::REVERSED),
+            (false, true) => Style::default().fg(theme.title).add_modifier(Modifier::BOLD),
+            (false, false) => Style::default(),
+        };
+        spans.push(Span::styled(label, style));
+    }
+    spans.push(Span::raw(suffix));
+    Spans::from(spans)
+}
+
+/// タブが複数あれば枠の下辺に見出しを並べて選ばれているものを目立たせ、クリックできるよう位置を覚えておく。
+fn draw_pane_tabs<B>(f: &mut Frame<B>, app: &mut App, index: usize, area: Rect)
+where
+    B: Backend,
+{
+    let pane = &app.panes[index];
+    if pane.dirs.len() == 1 || area.height < 2 {
+        return;
+    }
+    let theme = &app.config.theme;
+    let row = area.bottom() - 1;
+    let mut spans = Vec::new();
+    // 枠の角の次から書く
+    let mut x = area.x + 1;
+    let right = area.right().saturating_sub(1);
+    for (tab, title) in pane.tabs.titles.iter().enumerate() {
+        if x >= right {
+            break;
+        }
+        let title = names::middle_ellipsis(title, PANE_TAB_WIDTH);
+        let width = (title.width() as u16).min(right - x);
+        app.hits.pane_tabs.push((Rect::new(x, row, width, 1), index, tab));
+        let style = match tab == pane.tabs.index {
+            true => Style::default().fg(theme.tab_highlight).add_modifier(Modifier::BOLD),
+            false => Style::default().fg(theme.tab),
+        };
+        if tab > 0 {
+            spans.push(Span::raw("│"));
+        }
+        spans.push(Span::styled(title, style));
+        x += width + 1;
     }
+    let strip = Rect::new(area.x + 1, row, area.width.saturating_sub(2), 1);
+    f.render_widget(Paragraph::new(Spans::from(spans)), strip);
 }
 
-fn draw_text<B>(f: &mut Frame<B>, area: Rect)
+fn draw_prompt<B>(f: &mut Frame<B>, app: &App, area: Rect)
 where
     B: Backend,
 {
-    let text = vec![
-        Spans::from(vec![
-            Span::from("キー: "),
-        ]),
-        Spans::from(vec![
-            Span::raw("  key\""),
-            Span::styled("e", Style::default().add_modifier(Modifier::BOLD).fg(Color::Red)),
-            Span::raw("\": "),
-            Span::from("システムの終了"),
-        ]),
-        Spans::from(vec![
-            Span::raw("  key\""),
-            Span::styled("l", Style::default().add_modifier(Modifier::BOLD).fg(Color::Blue)),
-            Span::raw("\": "),
-            Span::from("右のペインにフォーカスする"),
-        ]),
-        Spans::from(vec![
-            Span::raw("  key\""),
-            Span::styled("h", Style::default().add_modifier(Modifier::BOLD).fg(Color::Green)),
-            Span::raw("\": "),
-            Span::from("左のペインにフォーカスする"),
-        ]),
-        Spans::from(
-            "One more thing is that it should display unicode characters: 10€"
+    let prompt = match &app.prompt {
+        Some(prompt) => prompt,
+        None => return,
+    };
+    let text = Spans::from(vec![
+        Span::styled(
+            prompt.kind.label(),
+            Style::default()
+                .fg(app.config.theme.title)
+                .add_modifier(Modifier::BOLD),
         ),
-    ];
-    let block = Block::default().borders(Borders::ALL).title(Span::styled(
-        "TaskManager_Ver1.0.0.0",
-        Style::default()
-            .fg(Color::Yellow)
-            .add_modifier(Modifier::BOLD),
-    ));
-    let paragraph = Paragraph::new(text).block(block).wrap(Wrap { trim: true });
-    f.render_widget(paragraph, area);
+        Span::raw(prompt.input.as_str()),
+        Span::styled(" ", Style::default().add_modifier(Modifier::REVERSED)),
+    ]);
+    let mut lines = vec![text];
+    // 枠の内側が 1 行しかなければ候補は出さない
+    if !prompt.candidates.is_empty() && area.height > 3 {
+        let candidates = prompt.candidates.iter().map(|candidate| {
+            // Tab で入れている候補を目立たせる
+            let style = match *candidate == prompt.input {
+                true => Style::default().add_modifier(Modifier::REVERSED),
+                false => Style::default().fg(Color::DarkGray),
+            };
+            vec![Span::styled(candidate_label(candidate), style), Span::raw("  ")]
+        });
+        lines.push(Spans::from(candidates.flatten().collect::<Vec<Span>>()));
+    }
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(app.config.theme.border));
+    f.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+/// パスの候補は最後の要素だけを出す（ディレクトリの区切り文字は残す）
+fn candidate_label(candidate: &str) -> &str {
+    let trimmed = candidate.trim_end_matches(['/', '\\']);
+    let start = trimmed.rfind(['/', '\\']).map(|i| i + 1).unwrap_or(0);
+    &candidate[start..]
+}
+
+/// 操作をあいまい検索して選ぶパレット。入力欄の下に候補とキーを並べる。
+/// ディレクトリへのジャンプでは点数の高い順にパスを並べる。
+fn draw_palette<B>(f: &mut Frame<B>, app: &App, area: Rect, layout: Responsive)
+where
+    B: Backend,
+{
+    let prompt = match &app.prompt {
+        Some(prompt) => prompt,
+        None => return,
+    };
+    let area = centered_rect(area, layout.overlay_percent(70), (area.height as u32 * 2 / 3) as u16);
+    f.render_widget(Clear, area);
+    let chunks = Layout::default()
+        .constraints([Constraint::Length(3), Constraint::Min(0)].as_ref())
+        .split(area);
+    draw_prompt(f, app, chunks[0]);
+
+    let width = chunks[1].width.saturating_sub(4) as usize;
+    let items: Vec<ListItem> = match prompt.kind {
+        PromptKind::Jump => prompt
+            .candidates
+            .iter()
+            .map(|dir| ListItem::new(names::middle_ellipsis(dir, width)))
+            .collect(),
+        _ => palette_items(app, &prompt.candidates),
+    };
+    let mut state = ListState::default();
+    state.select(Some(prompt.selected));
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(app.config.theme.border)),
+        )
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+        .highlight_symbol("> ");
+    f.render_stateful_widget(list, chunks[1], &mut state);
+}
+
+/// パレットの候補の操作名、キー、説明
+fn palette_items<'a>(app: &App, candidates: &[String]) -> Vec<ListItem<'a>> {
+    candidates
+        .iter()
+        .filter_map(|name| Action::from_name(name))
+        .map(|action| {
+            ListItem::new(Spans::from(vec![
+                Span::styled(
+                    format!("{:<14}", action.name()),
+                    Style::default().add_modifier(Modifier::BOLD),
+                ),
+                Span::styled(
+                    format!("{:<10}", app.keymap.keys_for(action).join(", ")),
+                    Style::default().fg(app.config.theme.highlight),
+                ),
+                Span::raw(action.description()),
+            ]))
+        })
+        .collect()
+}
+
+/// キーマップから作るキー一覧。何かキーを押すと閉じる。
+fn draw_help<B>(f: &mut Frame<B>, app: &App, area: Rect, layout: Responsive)
+where
+    B: Backend,
+{
+    let help = app.keymap.help();
+    let width = help
+        .iter()
+        .map(|(keys, _)| keys.chars().count())
+        .max()
+        .unwrap_or(0) as u16;
+    let widths = [Constraint::Length(width + 2), Constraint::Min(0)];
+    let area = centered_rect(area, layout.overlay_percent(70), help.len() as u16 + 2);
+    let rows = help.into_iter().map(|(keys, description)| {
+        Row::new(vec![
+            Cell::from(keys).style(
+                Style::default()
+                    .fg(app.config.theme.highlight)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Cell::from(description),
+        ])
+    });
+    let table = Table::new(rows)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(app.config.theme.border))
+                .title(Span::styled(
+                    "キー一覧",
+                    Style::default()
+                        .fg(app.config.theme.title)
+                        .add_modifier(Modifier::BOLD),
+                )),
+        )
+        .widths(&widths);
+    f.render_widget(Clear, area);
+    f.render_widget(table, area);
+}
+
+/// これまでのメッセージを新しい順に並べる。何かキーを押すと閉じる。
+fn draw_log<B>(f: &mut Frame<B>, app: &App, area: Rect, layout: Responsive)
+where
+    B: Backend,
+{
+    let theme = &app.config.theme;
+    let area = centered_rect(area, layout.overlay_percent(80), (area.height as u32 * 3 / 4) as u16);
+    let items: Vec<ListItem> = app
+        .logs
+        .items
+        .iter()
+        .map(|message| {
+            let (label, color) = match message.severity {
+                Severity::Info => ("INFO ", theme.info),
+                Severity::Warning => ("WARN ", theme.warning),
+                Severity::Error => ("ERROR", theme.error),
+            };
+            ListItem::new(Spans::from(vec![
+                Span::raw(format!("{:>6} ", message.age())),
+                Span::styled(label, Style::default().fg(color).add_modifier(Modifier::BOLD)),
+                Span::raw(format!(" {}", message.text)),
+            ]))
+        })
+        .collect();
+    let list = List::new(items).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(theme.border))
+            .title(Span::styled(
+                "メッセージ履歴",
+                Style::default().fg(theme.title).add_modifier(Modifier::BOLD),
+            )),
+    );
+    f.render_widget(Clear, area);
+    f.render_widget(list, area);
+}
+
+/// `area` の中央に、幅 `percent_x`%・高さ `height` 行（収まる範囲で）の領域を取る
+fn centered_rect(area: Rect, percent_x: u16, height: u16) -> Rect {
+    // u16 のまま掛けると広い端末で桁あふれする
+    let width = (area.width as u32 * percent_x as u32 / 100) as u16;
+    let height = height.min(area.height);
+    Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    )
 }
 
 fn draw_second_tab<B>(f: &mut Frame<B>, app: &mut App, area: Rect)
@@ -364,50 +877,104 @@ where
     f.render_widget(map, chunks[1]);
 }
 
-fn draw_third_tab<B>(f: &mut Frame<B>, _app: &mut App, area: Rect)
+/// 組み込みテーマの見本を並べる。`:theme <name>` で切り替えられる。
+fn draw_third_tab<B>(f: &mut Frame<B>, app: &mut App, area: Rect)
 where
     B: Backend,
 {
+    let names = Theme::bundled_names();
+    let constraints: Vec<Constraint> = names
+        .iter()
+        .map(|_| Constraint::Ratio(1, names.len() as u32))
+        .collect();
     let chunks = Layout::default()
         .direction(Direction::Horizontal)
-        .constraints([Constraint::Ratio(1, 2), Constraint::Ratio(1, 2)])
+        .constraints(constraints)
         .split(area);
-    let colors = [
-        Color::Reset,
-        Color::Black,
-        Color::Red,
-        Color::Green,
-        Color::Yellow,
-        Color::Blue,
-        Color::Magenta,
-        Color::Cyan,
-        Color::Gray,
-        Color::DarkGray,
-        Color::LightRed,
-        Color::LightGreen,
-        Color::LightYellow,
-        Color::LightBlue,
-        Color::LightMagenta,
-        Color::LightCyan,
-        Color::White,
+    for (name, chunk) in names.iter().zip(chunks) {
+        if let Some(theme) = Theme::bundled(name) {
+            let active = app.config.theme.name == *name;
+            draw_theme_preview(f, &theme, active, chunk);
+        }
+    }
+}
+
+fn draw_theme_preview<B>(f: &mut Frame<B>, theme: &Theme, active: bool, area: Rect)
+where
+    B: Backend,
+{
+    let title = match active {
+        true => format!("{} (使用中)", theme.name),
+        false => theme.name.clone(),
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.border))
+        .title(Span::styled(
+            title,
+            Style::default().fg(theme.title).add_modifier(Modifier::BOLD),
+        ));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let samples = [
+        ("src", EntryKind::Directory, false),
+        ("main.rs", EntryKind::File, false),
+        ("build.sh", EntryKind::Executable, false),
+        ("latest", EntryKind::Symlink, false),
+        (".gitignore", EntryKind::File, true),
     ];
-    let items: Vec<Row> = colors
-        .iter()
-        .map(|c| {
-            let cells = vec![
-                Cell::from(Span::raw(format!("{:?}: ", c))),
-                Cell::from(Span::styled("Foreground", Style::default().fg(*c))),
-                Cell::from(Span::styled("Background", Style::default().bg(*c))),
-            ];
-            Row::new(cells)
-        })
-        .collect();
-    let table = Table::new(items)
-        .block(Block::default().title("Colors").borders(Borders::ALL))
-        .widths(&[
-            Constraint::Ratio(1, 3),
-            Constraint::Ratio(1, 3),
-            Constraint::Ratio(1, 3),
-        ]);
-    f.render_widget(table, chunks[0]);
+    let mut lines = vec![Spans::from(vec![
+        Span::styled("Tab0 ", Style::default().fg(theme.tab_highlight)),
+        Span::styled("Tab1 Tab2", Style::default().fg(theme.tab)),
+    ])];
+    for (i, (name, kind, hidden)) in samples.iter().enumerate() {
+        // 見本は LS_COLORS に左右されないよう、テーマの色だけで塗る
+        let color = theme.entry_color(*kind, *hidden);
+        let line = match i {
+            1 => Spans::from(Span::styled(
+                format!("> {}", name),
+                Style::default()
+                    .fg(theme.highlight)
+                    .add_modifier(Modifier::BOLD),
+            )),
+            _ => Spans::from(Span::styled(format!("  {}", name), Style::default().fg(color))),
+        };
+        lines.push(line);
+    }
+    lines.push(Spans::from(Span::styled(
+        "エラーの表示",
+        Style::default().fg(theme.error),
+    )));
+    lines.push(Spans::from(Span::styled(
+        format!("{:<width$}", " ~/src  2/5", width = inner.width as usize),
+        theme.status_style(),
+    )));
+    f.render_widget(Paragraph::new(lines), inner);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::UserInterface::harness::Harness;
+    use std::path::Path;
+
+    #[test]
+    fn titles_a_pane_without_crumbs_with_its_tab() {
+        let mut harness = Harness::new(&["a.txt"]);
+        // 現在のディレクトリが読めないと `.` から始まり、正規化すると空になる
+        harness.app.focused_mut().dir = Some(names::normalize(Path::new(".")));
+        harness.app.hits.crumbs.clear();
+        let title = pane_title(&mut harness.app, 0, Rect::new(0, 0, 40, 10));
+        let tab = &harness.app.panes[0].tabs;
+        assert_eq!(title.0.len(), 1);
+        assert_eq!(title.0[0].content, tab.titles[tab.index]);
+        assert!(harness.app.hits.crumbs.is_empty());
+    }
+
+    #[test]
+    fn centers_overlays_on_very_wide_terminals() {
+        let area = Rect::new(0, 0, 1000, 60);
+        assert_eq!(centered_rect(area, 80, 30), Rect::new(100, 15, 800, 30));
+    }
 }
@@ -0,0 +1,112 @@
+use notify::{watcher, DebouncedEvent, RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// 外部プログラムによる変更をまとめて通知するまでの待ち時間
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// 各ペインが表示しているディレクトリを監視する。
+/// Linux では inotify、Windows では ReadDirectoryChangesW が使われる。
+pub struct DirWatcher {
+    watcher: RecommendedWatcher,
+    rx: mpsc::Receiver<DebouncedEvent>,
+    dirs: Vec<Option<PathBuf>>,
+}
+
+impl DirWatcher {
+    pub fn new(panes: usize) -> notify::Result<DirWatcher> {
+        let (tx, rx) = mpsc::channel();
+        Ok(DirWatcher {
+            watcher: watcher(tx, DEBOUNCE)?,
+            rx,
+            dirs: vec![None; panes],
+        })
+    }
+
+    pub fn dir(&self, index: usize) -> Option<&Path> {
+        self.dirs[index].as_deref()
+    }
+
+    /// ペインの監視対象を `dir` に差し替える。他のペインが見ていないディレクトリは監視を外す。
+    /// 監視を始められなくても `dir` を見ているものとして扱い、同じ失敗を繰り返さない。
+    pub fn watch(&mut self, index: usize, dir: &Path) -> notify::Result<()> {
+        if self.dir(index) == Some(dir) {
+            return Ok(());
+        }
+        if let Some(old) = self.dirs[index].take() {
+            if !self.is_watched(&old) {
+                let _ = self.watcher.unwatch(&old);
+            }
+        }
+        let result = match self.is_watched(dir) {
+            true => Ok(()),
+            false => self.watcher.watch(dir, RecursiveMode::NonRecursive),
+        };
+        self.dirs[index] = Some(dir.to_path_buf());
+        result
+    }
+
+    /// `index` 番目にペインを足す
+    pub fn insert(&mut self, index: usize) {
+        self.dirs.insert(index, None);
+    }
+
+    /// `index` 番目のペインを外す。他のペインが見ていなければ監視もやめる。
+    pub fn remove(&mut self, index: usize) {
+        if let Some(old) = self.dirs.remove(index) {
+            if !self.is_watched(&old) {
+                let _ = self.watcher.unwatch(&old);
+            }
+        }
+    }
+
+    fn is_watched(&self, dir: &Path) -> bool {
+        self.dirs.iter().flatten().any(|watched| watched == dir)
+    }
+
+    /// 前回呼び出し以降に中身が変わった監視中のディレクトリ
+    pub fn changed(&self) -> Vec<PathBuf> {
+        let mut changed: Vec<PathBuf> = Vec::new();
+        while let Ok(event) = self.rx.try_recv() {
+            let paths = match event {
+                DebouncedEvent::Create(path)
+                | DebouncedEvent::Write(path)
+                | DebouncedEvent::Chmod(path)
+                | DebouncedEvent::Remove(path) => vec![path],
+                DebouncedEvent::Rename(from, to) => vec![from, to],
+                DebouncedEvent::Rescan => self.dirs.iter().flatten().cloned().collect(),
+                _ => Vec::new(),
+            };
+            for path in paths {
+                let dir = self
+                    .dirs
+                    .iter()
+                    .flatten()
+                    .find(|dir| path == **dir || path.parent() == Some(dir.as_path()));
+                if let Some(dir) = dir {
+                    if !changed.contains(dir) {
+                        changed.push(dir.clone());
+                    }
+                }
+            }
+        }
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_a_failed_watch_once() {
+        let root = tempfile::tempdir().unwrap();
+        let missing = root.path().join("missing");
+        let mut watcher = DirWatcher::new(2).unwrap();
+        assert!(watcher.watch(0, &missing).is_err());
+        assert!(watcher.watch(0, &missing).is_ok());
+        assert_eq!(watcher.dir(0), Some(missing.as_path()));
+        assert!(watcher.watch(1, root.path()).is_ok());
+    }
+}
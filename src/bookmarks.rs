@@ -0,0 +1,46 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// 名前を付けて保存したディレクトリ。JSON のオブジェクト（名前 → パス）として保存する。
+#[derive(Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Bookmarks {
+    pub entries: BTreeMap<String, PathBuf>,
+}
+
+impl Bookmarks {
+    /// ファイルが無ければ空の一覧を返す
+    pub fn load(path: &Path) -> io::Result<Bookmarks> {
+        match fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Bookmarks::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let text = serde_json::to_string_pretty(self)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        fs::write(path, text)
+    }
+
+    pub fn add(&mut self, name: &str, dir: &Path) {
+        self.entries.insert(name.to_string(), dir.to_path_buf());
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<PathBuf> {
+        self.entries.remove(name)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Path> {
+        self.entries.get(name).map(PathBuf::as_path)
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.entries.keys().cloned().collect()
+    }
+}
@@ -0,0 +1,236 @@
+//! 端末を使わないサブコマンド。TUI と同じ設定・一覧の処理を使う。
+
+use crate::bookmarks::Bookmarks;
+use crate::config::{Config, SortKey};
+use crate::UserInterface::command::{ExportFormat, EXPORT_FORMATS};
+use crate::UserInterface::error::AppError;
+use crate::UserInterface::names;
+use crate::UserInterface::tasks::{self, DueDate};
+use crate::UserInterface::{read_listing, write_listing};
+use argh::FromArgs;
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, stdout, Write};
+use std::path::PathBuf;
+
+#[derive(Debug, FromArgs)]
+#[argh(subcommand)]
+pub enum Subcommand {
+    Ls(Ls),
+    Export(Export),
+    Bookmarks(BookmarksCommand),
+    Tasks(TasksCommand),
+}
+
+/// list a directory
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "ls")]
+pub struct Ls {
+    /// directory to list (default: the current directory)
+    #[argh(positional)]
+    dir: Option<PathBuf>,
+    /// sort key: name, size, mtime or extension (default: [view] sort)
+    #[argh(option, from_str_fn(parse_sort))]
+    sort: Option<SortKey>,
+    /// reverse the sort order
+    #[argh(switch, short = 'r')]
+    reverse: bool,
+    /// output format: txt, csv or json (default: txt)
+    #[argh(option, default = "ExportFormat::Txt", from_str_fn(parse_format))]
+    format: ExportFormat,
+}
+
+/// write a directory listing, as the export command of the TUI does
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "export")]
+pub struct Export {
+    /// directory to export
+    #[argh(positional)]
+    dir: PathBuf,
+    /// output format: csv, json or txt (default: csv)
+    #[argh(option, default = "ExportFormat::Csv", from_str_fn(parse_format))]
+    format: ExportFormat,
+    /// file to write instead of stdout
+    #[argh(option, short = 'o')]
+    output: Option<PathBuf>,
+}
+
+/// manage bookmarks saved in [general] bookmarks
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "bookmarks")]
+pub struct BookmarksCommand {
+    #[argh(subcommand)]
+    command: BookmarksSubcommand,
+}
+
+#[derive(Debug, FromArgs)]
+#[argh(subcommand)]
+enum BookmarksSubcommand {
+    List(BookmarksList),
+    Add(BookmarksAdd),
+    Rm(BookmarksRm),
+}
+
+/// print bookmarks as NAME<TAB>DIR
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "list")]
+struct BookmarksList {}
+
+/// save a directory under NAME
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "add")]
+struct BookmarksAdd {
+    /// bookmark name
+    #[argh(positional)]
+    name: String,
+    /// directory (default: the current directory)
+    #[argh(positional)]
+    dir: Option<PathBuf>,
+}
+
+/// remove a bookmark
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "rm")]
+struct BookmarksRm {
+    /// bookmark name
+    #[argh(positional)]
+    name: String,
+}
+
+/// query tasks
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "tasks")]
+pub struct TasksCommand {
+    #[argh(subcommand)]
+    command: TasksSubcommand,
+}
+
+#[derive(Debug, FromArgs)]
+#[argh(subcommand)]
+enum TasksSubcommand {
+    List(TasksList),
+}
+
+/// print tasks ordered by due date
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "list")]
+struct TasksList {
+    /// only tasks due before DATE (YYYY-MM-DD)
+    #[argh(option, from_str_fn(parse_date))]
+    due_before: Option<DueDate>,
+    /// output format: txt or json (default: txt)
+    #[argh(option, default = "ExportFormat::Txt", from_str_fn(parse_format))]
+    format: ExportFormat,
+}
+
+fn parse_sort(value: &str) -> Result<SortKey, String> {
+    SortKey::from_name(value)
+        .ok_or_else(|| format!("{} のいずれかを指定してください", SortKey::NAMES.join(" | ")))
+}
+
+fn parse_format(value: &str) -> Result<ExportFormat, String> {
+    ExportFormat::from_name(value)
+        .ok_or_else(|| format!("{} のいずれかを指定してください", EXPORT_FORMATS.join(" | ")))
+}
+
+fn parse_date(value: &str) -> Result<DueDate, String> {
+    DueDate::parse(value)
+}
+
+fn current_dir() -> Result<PathBuf, AppError> {
+    std::env::current_dir().map_err(|e| AppError::Io(PathBuf::from("."), e))
+}
+
+/// サブコマンドを実行する。`fexp ls | head` のように読み手が先に終わっても失敗にしない。
+pub fn run(command: Subcommand, config: Config) -> Result<(), Box<dyn Error>> {
+    match execute(command, config) {
+        Err(e) if is_broken_pipe(e.as_ref()) => Ok(()),
+        result => result,
+    }
+}
+
+fn is_broken_pipe(e: &(dyn Error + 'static)) -> bool {
+    e.downcast_ref::<io::Error>()
+        .map(|e| e.kind() == io::ErrorKind::BrokenPipe)
+        .unwrap_or(false)
+}
+
+fn execute(command: Subcommand, config: Config) -> Result<(), Box<dyn Error>> {
+    let mut out = stdout();
+    match command {
+        Subcommand::Ls(ls) => {
+            let dir = match ls.dir {
+                Some(dir) => dir,
+                None => current_dir()?,
+            };
+            let mut view = config.view;
+            view.sort = ls.sort.unwrap_or(view.sort);
+            view.reverse ^= ls.reverse;
+            let items = read_listing(&dir, &view).map_err(|e| AppError::Io(dir.clone(), e))?;
+            write_listing(&mut out, &items, ls.format)?;
+        }
+        Subcommand::Export(export) => {
+            let dir = export.dir;
+            let items = read_listing(&dir, &config.view).map_err(|e| AppError::Io(dir.clone(), e))?;
+            match export.output {
+                Some(file) => {
+                    let mut writer = File::create(&file).map_err(|e| AppError::Io(file.clone(), e))?;
+                    write_listing(&mut writer, &items, export.format)
+                        .map_err(|e| AppError::Io(file.clone(), e))?;
+                }
+                None => write_listing(&mut out, &items, export.format)?,
+            }
+        }
+        Subcommand::Bookmarks(bookmarks) => {
+            let file = &config.general.bookmarks;
+            let mut store = Bookmarks::load(file).map_err(|e| AppError::Io(file.clone(), e))?;
+            match bookmarks.command {
+                BookmarksSubcommand::List(_) => {
+                    for (name, dir) in &store.entries {
+                        writeln!(out, "{}\t{}", name, names::escape_path(dir))?;
+                    }
+                    return Ok(());
+                }
+                BookmarksSubcommand::Add(add) => {
+                    // TUI と同じく絶対パスで保存する
+                    let cwd = current_dir()?;
+                    let dir = match add.dir {
+                        Some(dir) => cwd.join(dir),
+                        None => cwd,
+                    };
+                    if !dir.is_dir() {
+                        return Err(AppError::NotADirectory(dir).into());
+                    }
+                    store.add(&add.name, &dir);
+                }
+                BookmarksSubcommand::Rm(rm) => {
+                    if store.remove(&rm.name).is_none() {
+                        return Err(AppError::Command(format!("ブックマーク `{}` はありません", rm.name)).into());
+                    }
+                }
+            }
+            store.save(file).map_err(|e| AppError::Io(file.clone(), e))?;
+        }
+        Subcommand::Tasks(tasks_command) => match tasks_command.command {
+            TasksSubcommand::List(list) => {
+                let items: Vec<tasks::DueTask> = tasks::all()
+                    .into_iter()
+                    .filter(|task| list.due_before.map(|date| task.due < date).unwrap_or(true))
+                    .collect();
+                match list.format {
+                    ExportFormat::Json => {
+                        serde_json::to_writer_pretty(&mut out, &items).map_err(io::Error::from)?;
+                        writeln!(out)?;
+                    }
+                    _ => {
+                        for task in &items {
+                            writeln!(out, "{}\t{}\t{}", task.due, task.name, task.client)?;
+                        }
+                    }
+                }
+            }
+        },
+    }
+    out.flush()?;
+    Ok(())
+}
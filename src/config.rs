@@ -0,0 +1,252 @@
+use crate::UserInterface::keymap::{KeyBinding, Keymap};
+use crate::UserInterface::theme::{Theme, ThemeSpec};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// 設定ファイル。書かれていない項目は組み込みの既定値が使われる。
+#[derive(Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub general: General,
+    pub view: View,
+    /// `[theme]` に書かれた内容
+    #[serde(rename = "theme")]
+    pub theme_spec: ThemeSpec,
+    /// `theme_spec` から組み立てたテーマ
+    #[serde(skip)]
+    pub theme: Theme,
+    /// 操作名とキーの対応。書いた操作は既定のキーを置き換える。
+    pub keys: BTreeMap<String, KeyBinding>,
+    pub openers: Vec<OpenerRule>,
+    /// 設定ファイルのあるディレクトリ（テーマファイルを探す場所）
+    #[serde(skip)]
+    pub dir: Option<PathBuf>,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct General {
+    /// 起動時に各ペインで開くディレクトリ。足りない分は最後の要素を使い、空なら作業ディレクトリ。
+    pub start_dirs: Vec<PathBuf>,
+    /// 数字キーのショートカットを定義した JSON
+    pub shortcuts: PathBuf,
+    /// `quit` で一覧を書き出すファイル
+    pub export: PathBuf,
+    /// `:bookmark` で保存するブックマークの JSON
+    pub bookmarks: PathBuf,
+    /// マウスを使う。使わなければ端末の文字列選択がそのまま使える。
+    pub mouse: bool,
+    /// 終了時の状態を保存し、次の起動で戻す（`--session` を指定すれば常に使う）
+    pub restore_session: bool,
+    /// セッションを保存するディレクトリ。省略するとデータディレクトリの `fexp/sessions`。
+    pub session_dir: Option<PathBuf>,
+    /// 訪れたディレクトリを記録するファイル。省略するとデータディレクトリの `fexp/frecency.json`。
+    pub frecency: Option<PathBuf>,
+}
+
+impl Default for General {
+    fn default() -> General {
+        General {
+            start_dirs: Vec::new(),
+            shortcuts: PathBuf::from("PathShortCut.json"),
+            export: PathBuf::from("task.txt"),
+            bookmarks: PathBuf::from("bookmarks.json"),
+            mouse: true,
+            restore_session: true,
+            session_dir: None,
+            frecency: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortKey {
+    Name,
+    Size,
+    Mtime,
+    Extension,
+}
+
+impl SortKey {
+    pub const NAMES: [&'static str; 4] = ["name", "size", "mtime", "extension"];
+
+    pub fn from_name(name: &str) -> Option<SortKey> {
+        match name {
+            "name" => Some(SortKey::Name),
+            "size" => Some(SortKey::Size),
+            "mtime" => Some(SortKey::Mtime),
+            "extension" => Some(SortKey::Extension),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct View {
+    pub sort: SortKey,
+    pub reverse: bool,
+    pub dirs_first: bool,
+    pub show_hidden: bool,
+    pub display: DisplayMode,
+    /// 起動時のペインの数
+    pub panes: usize,
+    pub split: Split,
+}
+
+impl Default for View {
+    fn default() -> View {
+        View {
+            sort: SortKey::Name,
+            reverse: false,
+            dirs_first: true,
+            show_hidden: true,
+            display: DisplayMode::Name,
+            panes: 2,
+            split: Split::Auto,
+        }
+    }
+}
+
+/// ペインの並べ方
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Split {
+    /// 横に並べ、狭い端末では縦に積む
+    Auto,
+    /// 常に横に並べる
+    Columns,
+    /// 常に縦に積む
+    Rows,
+}
+
+/// 一覧に出すエントリの名前
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DisplayMode {
+    /// ファイル名だけ
+    Name,
+    /// 作業ディレクトリからの相対パス
+    Relative,
+    /// 絶対パス
+    Full,
+}
+
+impl DisplayMode {
+    pub const NAMES: [&'static str; 3] = ["name", "relative", "full"];
+
+    pub fn from_name(name: &str) -> Option<DisplayMode> {
+        match name {
+            "name" => Some(DisplayMode::Name),
+            "relative" => Some(DisplayMode::Relative),
+            "full" => Some(DisplayMode::Full),
+            _ => None,
+        }
+    }
+}
+
+/// 拡張子に応じてファイルを開くコマンド。パスは `args` の後ろに渡される。
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OpenerRule {
+    pub extensions: Vec<String>,
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+impl OpenerRule {
+    pub fn matches(&self, path: &Path) -> bool {
+        match path.extension() {
+            Some(extension) => {
+                let extension = extension.to_string_lossy().to_lowercase();
+                self.extensions
+                    .iter()
+                    .any(|e| e.trim_start_matches('.').to_lowercase() == extension)
+            }
+            None => false,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, toml::de::Error),
+    Invalid(PathBuf, String),
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            ConfigError::Parse(path, e) => write!(f, "{}: {}", path.display(), e),
+            ConfigError::Invalid(path, e) => write!(f, "{}: {}", path.display(), e),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    /// `<設定ディレクトリ>/fexp/config.toml`。
+    /// Linux では `$XDG_CONFIG_HOME`（既定は `~/.config`）、Windows では `%APPDATA%`。
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("fexp").join("config.toml"))
+    }
+
+    /// 設定を読み込む。`path` を省略したときは既定の場所を探し、無ければ既定値を使う。
+    pub fn load(path: Option<&Path>) -> Result<Config, ConfigError> {
+        let (path, required) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => match Config::default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Config::default()),
+            },
+        };
+        let dir = path.parent().map(Path::to_path_buf);
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if !required && e.kind() == io::ErrorKind::NotFound => {
+                return Ok(Config {
+                    dir,
+                    ..Config::default()
+                })
+            }
+            Err(e) => return Err(ConfigError::Io(path, e)),
+        };
+        let mut config: Config =
+            toml::from_str(&text).map_err(|e| ConfigError::Parse(path.clone(), e))?;
+        config.validate().map_err(|e| ConfigError::Invalid(path.clone(), e))?;
+        config.theme =
+            Theme::resolve(&config.theme_spec, dir.as_deref()).map_err(|e| ConfigError::Invalid(path, e))?;
+        config.dir = dir;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        Keymap::new(&self.keys)?;
+        if self.view.panes == 0 {
+            return Err(String::from("[view] panes must be at least 1"));
+        }
+        for rule in &self.openers {
+            if rule.command.is_empty() {
+                return Err(String::from("[[openers]] command must not be empty"));
+            }
+        }
+        Ok(())
+    }
+
+    /// `index` 番目のペインの開始ディレクトリ
+    pub fn start_dir(&self, index: usize) -> PathBuf {
+        match self.general.start_dirs.get(index).or_else(|| self.general.start_dirs.last()) {
+            Some(dir) => dir.clone(),
+            None => PathBuf::from("."),
+        }
+    }
+}
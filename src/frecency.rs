@@ -0,0 +1,151 @@
+use crate::UserInterface::names::normalize;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// 覚えておくディレクトリの数。超えたら点数の低いものから忘れる。
+const MAX_ENTRIES: usize = 1000;
+
+const HOUR: u64 = 60 * 60;
+const DAY: u64 = 24 * HOUR;
+const WEEK: u64 = 7 * DAY;
+
+/// 1 つのディレクトリへの訪問
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Visit {
+    pub count: u32,
+    /// 最後に訪れた UNIX 時刻（秒）
+    pub last: u64,
+}
+
+impl Visit {
+    /// 回数に、最近訪れたものほど大きい重みを掛けた点数（`z` と同じ考え方）
+    pub fn score(&self, now: u64) -> f64 {
+        let weight = match now.saturating_sub(self.last) {
+            age if age < HOUR => 4.0,
+            age if age < DAY => 2.0,
+            age if age < WEEK => 0.5,
+            _ => 0.25,
+        };
+        self.count as f64 * weight
+    }
+}
+
+/// 訪れたディレクトリの記録。JSON のオブジェクト（パス → 訪問）として保存する。
+#[derive(Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Frecency {
+    pub entries: BTreeMap<PathBuf, Visit>,
+}
+
+impl Frecency {
+    /// ファイルが無ければ空の記録を返す
+    pub fn load(path: &Path) -> io::Result<Frecency> {
+        match fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Frecency::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let text = serde_json::to_string_pretty(self)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        fs::write(path, text)
+    }
+
+    /// 訪問を記録する。`.` と `..` は取り除き、JSON に書けない UTF-8 でないパスは記録しない。
+    pub fn visit(&mut self, dir: &Path, now: u64) {
+        if dir.to_str().is_none() {
+            return;
+        }
+        let visit = self.entries.entry(normalize(dir)).or_insert(Visit { count: 0, last: now });
+        visit.count += 1;
+        visit.last = now;
+        if self.entries.len() > MAX_ENTRIES {
+            let forgotten = self
+                .entries
+                .iter()
+                .min_by(|a, b| a.1.score(now).total_cmp(&b.1.score(now)))
+                .map(|(dir, _)| dir.clone());
+            if let Some(dir) = forgotten {
+                self.entries.remove(&dir);
+            }
+        }
+    }
+
+    /// `query` に一致する、今もあるディレクトリを点数の高い順に並べる。
+    /// 空白で区切った語がパスにこの順で現れ、最後の語は末尾の名前に含まれるものを一致とみなす。
+    pub fn rank(&self, query: &str, now: u64) -> Vec<PathBuf> {
+        let terms: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+        let mut matches: Vec<(f64, &PathBuf)> = self
+            .entries
+            .iter()
+            .filter(|(dir, _)| matches(dir, &terms) && dir.is_dir())
+            .map(|(dir, visit)| (visit.score(now), dir))
+            .collect();
+        matches.sort_by(|a, b| b.0.total_cmp(&a.0));
+        matches.into_iter().map(|(_, dir)| dir.clone()).collect()
+    }
+}
+
+fn matches(dir: &Path, terms: &[String]) -> bool {
+    let path = dir.to_string_lossy().to_lowercase();
+    let mut position = 0;
+    for term in terms {
+        match path[position..].find(term.as_str()) {
+            Some(found) => position += found + term.len(),
+            None => return false,
+        }
+    }
+    match (terms.last(), dir.file_name()) {
+        (Some(last), Some(name)) => name.to_string_lossy().to_lowercase().contains(last.as_str()),
+        _ => true,
+    }
+}
+
+/// 今の UNIX 時刻（秒）
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranks_frequent_and_recent_directories_first() {
+        let root = tempfile::tempdir().unwrap();
+        for dir in ["work/api", "work/web", "old/api"] {
+            fs::create_dir_all(root.path().join(dir)).unwrap();
+        }
+        let now = 10 * WEEK;
+        let mut frecency = Frecency::default();
+        for _ in 0..3 {
+            frecency.visit(&root.path().join("old/api"), now - 2 * WEEK);
+        }
+        frecency.visit(&root.path().join("work/api"), now - 10);
+        frecency.visit(&root.path().join("old/../work/./web"), now - 10);
+        frecency.visit(&root.path().join("gone/api"), now);
+
+        assert_eq!(
+            frecency.rank("api", now),
+            vec![root.path().join("work/api"), root.path().join("old/api")]
+        );
+        assert_eq!(frecency.rank("wo a", now), vec![root.path().join("work/api")]);
+        // 最後の語は末尾の名前に含まれていなければならない
+        assert!(frecency.rank("work", now).is_empty());
+        assert_eq!(frecency.entries[&root.path().join("old/api")].count, 3);
+        assert!(frecency.entries.contains_key(&root.path().join("work/web")));
+    }
+}
@@ -1,30 +1,32 @@
-#[allow(dead_code)]
+mod bookmarks;
+mod cli;
+mod config;
+mod frecency;
+#[allow(non_snake_case)]
 mod UserInterface;
-#[allow(dead_code)]
+mod session;
+mod terminal;
 mod util;
 
-use crate::UserInterface::{ui, App, Task};
-use once_cell::sync::OnceCell;
+use crate::config::Config;
+use crate::session::Session;
+use crate::terminal::TerminalGuard;
+#[cfg(unix)]
+use crate::util::event::SuspendSignal;
+use crate::util::event::{Events, TerminalInput, Ticker};
+use crate::UserInterface::status::Severity;
+use crate::UserInterface::{names, ui, App};
 use argh::FromArgs;
-use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event as CEvent, KeyCode, KeyEvent, KeyModifiers},
-    execute,
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
-};
 use std::{
     error::Error,
-    io::stdout,
-    sync::mpsc,
-    thread,
-    time::{Duration, Instant},
+    fs,
+    io::{stdout, Write},
+    path::PathBuf,
+    process,
+    time::Duration,
 };
 use tui::{backend::CrosstermBackend, Terminal};
 
-enum Event<I> {
-    Input(I),
-    Tick,
-}
-
 /// Crossterm demo
 #[derive(Debug, FromArgs)]
 struct Cli {
@@ -34,123 +36,134 @@ struct Cli {
     /// whether unicode symbols are used to improve the overall look of the app
     #[argh(option, default = "true")]
     enhanced_graphics: bool,
+    /// config file to use instead of <config dir>/fexp/config.toml
+    #[argh(option)]
+    config: Option<PathBuf>,
+    /// write the focused pane's directory to FILE on exit (for cd-on-exit)
+    #[argh(option)]
+    choosedir: Option<PathBuf>,
+    /// picker mode: print the chosen or marked paths to stdout and exit
+    #[argh(switch)]
+    pick: bool,
+    /// restore and save the named session instead of the default one
+    #[argh(option)]
+    session: Option<String>,
+    #[argh(subcommand)]
+    command: Option<cli::Subcommand>,
 }
 
-// TODO:日本語対応
-pub fn read_line() -> Result<String, Box<dyn Error>> {
-    let mut line = String::new();
-    while let CEvent::Key(KeyEvent { code, .. }) = event::read()? {
-        match code {
-            KeyCode::Enter => {
-                break;
-            }
-            KeyCode::Char(c) => {
-                line.push(c);
-            }
-            _ => {}
-        }
-    }
-
-    Ok(line)
-}
+/// 選択モードで何も選ばずに終了したときの終了コード
+const EXIT_CANCELLED: i32 = 130;
 
 fn main() -> Result<(), Box<dyn Error>> {
 
     let cli: Cli = argh::from_env();
 
-    enable_raw_mode()?;
+    // 設定の誤りは端末を切り替える前に報告する
+    let config = match Config::load(cli.config.as_deref()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("fexp: invalid configuration: {}", e);
+            process::exit(2);
+        }
+    };
+
+    // サブコマンドは端末を切り替えずに実行する
+    if let Some(command) = cli.command {
+        if let Err(e) = cli::run(command, config) {
+            eprintln!("fexp: {}", e);
+            process::exit(1);
+        }
+        return Ok(());
+    }
 
-    let mut stdout = stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    match run(cli, config) {
+        Ok(0) => Ok(()),
+        Ok(code) => process::exit(code),
+        Err(e) => {
+            // ここに来た時点で端末は元に戻っている
+            eprintln!("fexp: {}", e);
+            process::exit(1);
+        }
+    }
+}
 
-    let backend = CrosstermBackend::new(stdout);
+/// 終了コードを返す
+fn run(cli: Cli, config: Config) -> Result<i32, Box<dyn Error>> {
+    // 選択モードは作業ディレクトリから始めたいので、名前を指定したときだけ使う
+    let session_name = match (&cli.session, config.general.restore_session && !cli.pick) {
+        (Some(name), _) => Some(name.as_str()),
+        (None, true) => Some(session::DEFAULT_NAME),
+        (None, false) => None,
+    };
+    let session_file = session_name
+        .map(|name| session::file(config.general.session_dir.as_deref(), name))
+        .transpose()?;
+
+    terminal::install_panic_hook();
+    let guard = TerminalGuard::enter(cli.pick, config.general.mouse)?;
+
+    let backend = CrosstermBackend::new(terminal::output());
 
     let mut terminal = Terminal::new(backend)?;
 
-    // Setup input handling
-    let (tx, rx) = mpsc::channel();
-
-    let tick_rate = Duration::from_millis(cli.tick_rate);
-    thread::spawn(move || {
-        let mut last_tick = Instant::now();
-        loop {
-            // poll for tick rate duration, if no events, sent tick event.
-            let timeout = tick_rate
-                .checked_sub(last_tick.elapsed())
-                .unwrap_or_else(|| Duration::from_secs(0));
-            if event::poll(timeout).unwrap() {
-                if let CEvent::Key(key) = event::read().unwrap() {
-                    tx.send(Event::Input(key)).unwrap();
-                }
-            }
-            if last_tick.elapsed() >= tick_rate {
-                tx.send(Event::Tick).unwrap();
-                last_tick = Instant::now();
-            }
+    let events = Events::new();
+    events.spawn(TerminalInput);
+    events.spawn(Ticker(Duration::from_millis(cli.tick_rate)));
+    #[cfg(unix)]
+    events.spawn(SuspendSignal::new()?);
+
+    let mut app = App::new("Crossterm Demo", cli.enhanced_graphics, config, Some(events.sender()));
+    app.pick_mode = cli.pick;
+    if let Some(file) = &session_file {
+        match Session::load(file) {
+            Ok(Some(session)) => app.restore_session(session),
+            Ok(None) => {}
+            Err(e) => app.notify(Severity::Warning, format!("{} を読めません: {}", file.display(), e)),
         }
-    });
-
-    let mut app = App::new("Crossterm Demo", cli.enhanced_graphics);
+    }
 
     terminal.clear()?;
 
     loop {
         terminal.draw(|f| ui::draw(f, &mut app))?;
-        match rx.recv()? {
-            Event::Input(event) => match event.modifiers {
-                KeyModifiers::NONE => {
-                    match event.code {
-                        // TODO:本当は、on_keyの実装はmain内でやるべき？'/'の実装はappでやりたいが。。
-                        KeyCode::Char(c) => {
-                            if c != '/' {
-                                app.on_key(c, terminal.get_cursor().unwrap());
-                            } else {
-                                let search = read_line().unwrap();
-                                app.search_string_in_this_path(&search);
-                            }
-                        }
-                        KeyCode::Left => app.on_left(),
-                        KeyCode::Up => app.on_up(),
-                        KeyCode::Right => app.on_right(),
-                        KeyCode::Down => app.on_down(),
-                        KeyCode::Enter => app.on_enter_dir(),
-                        KeyCode::Esc => app.on_all_disp(),
-                        _ => {},
-                    }
-                },
-                _ => {
-                    match event {
-                        KeyEvent {
-                            code: KeyCode::Char('d'),
-                            modifiers: KeyModifiers::CONTROL,
-                        } => { for i in 0..4 { app.on_down() }},
-                        KeyEvent {
-                            code: KeyCode::Char('u'),
-                            modifiers: KeyModifiers::CONTROL,
-                        } => { for i in 0..4 { app.on_up() }},
-                        KeyEvent {
-                            code: KeyCode::Char('c'),
-                            modifiers: KeyModifiers::CONTROL,
-                        } => { app.copy_path(); },
-                        _ => {},
-                    }
-                }
-            },
-            Event::Tick => {
-                app.on_tick();
-            }
+        app.on_event(events.next()?);
+        if app.suspend_requested {
+            app.suspend_requested = false;
+            guard.suspend()?;
+            // 再開後は画面が他のプログラムの出力で上書きされている
+            terminal.clear()?;
         }
         if app.should_quit {
-            disable_raw_mode()?;
-            execute!(
-                terminal.backend_mut(),
-                LeaveAlternateScreen,
-                DisableMouseCapture
-            )?;
-            terminal.show_cursor()?;
             break;
         }
     }
 
-    Ok(())
+    drop(terminal);
+    drop(guard);
+
+    if let Some(file) = &session_file {
+        if let Err(e) = app.session().save(file) {
+            eprintln!("fexp: cannot save the session to {}: {}", file.display(), e);
+        }
+    }
+    if let Err(e) = app.save_frecency() {
+        eprintln!("fexp: {}", e);
+    }
+
+    if let Some(file) = &cli.choosedir {
+        let mut out = fs::File::create(file)?;
+        names::write_path(&mut out, &app.current_dir())?;
+    }
+    if cli.pick {
+        if app.picked.is_empty() {
+            return Ok(EXIT_CANCELLED);
+        }
+        let mut out = stdout();
+        for path in &app.picked {
+            names::write_path(&mut out, path)?;
+            out.write_all(b"\n")?;
+        }
+    }
+    Ok(0)
 }
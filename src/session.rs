@@ -0,0 +1,102 @@
+use crate::config::SortKey;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// 名前を指定しなかったときのセッション
+pub const DEFAULT_NAME: &str = "default";
+
+/// 終了時の画面の状態。次に同じ名前で起動したときに元に戻す。
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    /// 上に並ぶタブ
+    pub tab: usize,
+    /// フォーカス中のペイン
+    pub focus: usize,
+    pub sort: SortKey,
+    pub reverse: bool,
+    pub panes: Vec<PaneSession>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct PaneSession {
+    /// 選ばれているディレクトリのタブ
+    pub tab: usize,
+    pub dirs: Vec<DirSession>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct DirSession {
+    pub dir: PathBuf,
+    /// カーソルのあったエントリ
+    pub selected: Option<PathBuf>,
+    pub filter: Option<String>,
+}
+
+impl Session {
+    /// ファイルが無ければ None を返す
+    pub fn load(path: &Path) -> io::Result<Option<Session>> {
+        match fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text)
+                .map(Some)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let text = serde_json::to_string_pretty(self)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        fs::write(path, text)
+    }
+}
+
+/// `name` のセッションを保存するファイル。`dir` を省略するとデータディレクトリの `fexp/sessions`
+/// （Linux では `~/.local/share`、Windows では `%APPDATA%`）。
+pub fn file(dir: Option<&Path>, name: &str) -> Result<PathBuf, String> {
+    if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+        return Err(format!("invalid session name `{}`", name));
+    }
+    let dir = match dir {
+        Some(dir) => dir.to_path_buf(),
+        None => dirs::data_dir()
+            .map(|dir| dir.join("fexp").join("sessions"))
+            .ok_or_else(|| String::from("no data directory; set general.session_dir"))?,
+    };
+    Ok(dir.join(format!("{}.json", name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saves_and_loads_sessions_by_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = file(Some(dir.path()), "work").unwrap();
+        assert_eq!(Session::load(&path).unwrap(), None);
+
+        let session = Session {
+            tab: 1,
+            focus: 0,
+            sort: SortKey::Size,
+            reverse: true,
+            panes: vec![PaneSession {
+                tab: 0,
+                dirs: vec![DirSession {
+                    dir: dir.path().to_path_buf(),
+                    selected: None,
+                    filter: Some(String::from("rs")),
+                }],
+            }],
+        };
+        session.save(&path).unwrap();
+        assert_eq!(Session::load(&path).unwrap(), Some(session));
+        assert!(file(None, "../etc").is_err());
+    }
+}
@@ -0,0 +1,92 @@
+//! 端末の状態（生モード・代替画面）の出入り
+
+use crossterm::{
+    cursor,
+    event::{DisableMouseCapture, EnableMouseCapture},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use std::io::{stderr, stdout, Write};
+use std::panic;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// 端末を切り替えている間だけ true
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+/// 画面を標準エラーに描く（標準出力は結果を返すのに使う）
+static TO_STDERR: AtomicBool = AtomicBool::new(false);
+/// マウスの操作を受け取る
+static MOUSE: AtomicBool = AtomicBool::new(true);
+
+/// 画面を描く出力先
+pub fn output() -> Box<dyn Write + Send> {
+    match TO_STDERR.load(Ordering::SeqCst) {
+        true => Box::new(stderr()),
+        false => Box::new(stdout()),
+    }
+}
+
+fn enter() -> crossterm::Result<()> {
+    enable_raw_mode()?;
+    ACTIVE.store(true, Ordering::SeqCst);
+    execute!(output(), EnterAlternateScreen)?;
+    if MOUSE.load(Ordering::SeqCst) {
+        execute!(output(), EnableMouseCapture)?;
+    }
+    Ok(())
+}
+
+/// 端末を元に戻す。何度呼んでもよい。
+pub fn restore() {
+    if ACTIVE.swap(false, Ordering::SeqCst) {
+        let _ = execute!(output(), LeaveAlternateScreen, DisableMouseCapture, cursor::Show);
+        let _ = disable_raw_mode();
+    }
+}
+
+/// 生モードと代替画面を有効にし、破棄されるときに必ず元へ戻す。
+/// エラーで抜けても、パニックで巻き戻っても端末が壊れたまま残らない。
+pub struct TerminalGuard;
+
+impl TerminalGuard {
+    /// `to_stderr` なら画面を標準エラーに描く。`$(fexp --pick)` のように標準出力を
+    /// 受け取られていても画面が見える。
+    pub fn enter(to_stderr: bool, mouse: bool) -> crossterm::Result<TerminalGuard> {
+        TO_STDERR.store(to_stderr, Ordering::SeqCst);
+        MOUSE.store(mouse, Ordering::SeqCst);
+        // 途中で失敗しても、返す前の Drop で元に戻る
+        let guard = TerminalGuard;
+        enter()?;
+        Ok(guard)
+    }
+
+    /// シェルに戻って一時停止し、`fg` で再開したら端末を切り替え直す。
+    /// 再開後は画面を描き直す必要がある。
+    #[cfg(unix)]
+    pub fn suspend(&self) -> crossterm::Result<()> {
+        restore();
+        // 既定の動作（停止）を呼ぶ。SIGCONT を受けるとここから戻る。
+        let _ = signal_hook::low_level::emulate_default_handler(signal_hook::consts::SIGTSTP);
+        enter()
+    }
+
+    #[cfg(not(unix))]
+    pub fn suspend(&self) -> crossterm::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        restore();
+    }
+}
+
+/// パニックしたら、メッセージを出す前に端末を戻す。
+/// 代替画面のまま出力するとメッセージが画面と一緒に消えてしまう。
+pub fn install_panic_hook() {
+    let default = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        restore();
+        default(info);
+    }));
+}
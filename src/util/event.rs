@@ -1,75 +1,189 @@
-use std::io;
+use crate::UserInterface::status::Severity;
+use crossterm::event::{self as terminal_event, Event as TerminalEvent, KeyEvent, MouseEvent};
 use std::sync::mpsc;
 use std::thread;
 use std::time::Duration;
 
-use termion::event::Key;
-use termion::input::TermRead;
-
-pub enum Event<I> {
-    Input(I),
+/// メインループに届くイベント。入力も tick も別スレッドの知らせも 1 つのチャンネルで受け取る。
+#[derive(Debug)]
+pub enum Event {
+    Key(KeyEvent),
+    Mouse(MouseEvent),
+    /// 端末の大きさが変わった。大きさは次の描画で `Frame::size` から読む。
+    Resize,
     Tick,
+    /// 外から SIGTSTP を受けた
+    Suspend,
+    Job(Job),
+    /// 端末から入力を読めなくなった
+    InputClosed,
 }
 
-/// A small event handler that wrap termion input and tick events. Each event
-/// type is handled in its own thread and returned to a common `Receiver`
-pub struct Events {
-    rx: mpsc::Receiver<Event<Key>>,
-    input_handle: thread::JoinHandle<()>,
-    tick_handle: thread::JoinHandle<()>,
+/// 別スレッドで動く処理からの知らせ
+#[derive(Debug, PartialEq)]
+pub enum Job {
+    /// 結果が届いた。次の tick を待たずに取り込む。
+    Progress,
+    /// ステータスバーと履歴に出すメッセージ（使用量アナライザでの削除の結果など）
+    Message(Severity, String),
+}
+
+/// イベントを送るもの。`Events::spawn` で専用のスレッドで動かす。
+pub trait Source: Send + 'static {
+    /// 送り終わるか、受け手がいなくなるまで送る
+    fn run(self, tx: mpsc::Sender<Event>);
 }
 
-#[derive(Debug, Clone, Copy)]
-pub struct Config {
-    pub tick_rate: Duration,
+/// 端末の入力（キー、マウス、大きさの変更）
+pub struct TerminalInput;
+
+impl Source for TerminalInput {
+    fn run(self, tx: mpsc::Sender<Event>) {
+        while let Ok(event) = terminal_event::read() {
+            let event = match event {
+                TerminalEvent::Key(key) => Event::Key(key),
+                TerminalEvent::Mouse(mouse) => Event::Mouse(mouse),
+                TerminalEvent::Resize(_, _) => Event::Resize,
+            };
+            if tx.send(event).is_err() {
+                return;
+            }
+        }
+        let _ = tx.send(Event::InputClosed);
+    }
 }
 
-impl Default for Config {
-    fn default() -> Config {
-        Config {
-            tick_rate: Duration::from_millis(250),
+/// 一定間隔の tick
+pub struct Ticker(pub Duration);
+
+impl Source for Ticker {
+    fn run(self, tx: mpsc::Sender<Event>) {
+        loop {
+            thread::sleep(self.0);
+            if tx.send(Event::Tick).is_err() {
+                return;
+            }
         }
     }
 }
 
+/// SIGTSTP。生モードでは Ctrl+Z はキーとして届くが、`kill -TSTP` などで外から止められることもある。
+#[cfg(unix)]
+pub struct SuspendSignal(signal_hook::iterator::Signals);
+
+#[cfg(unix)]
+impl SuspendSignal {
+    pub fn new() -> std::io::Result<SuspendSignal> {
+        signal_hook::iterator::Signals::new([signal_hook::consts::SIGTSTP]).map(SuspendSignal)
+    }
+}
+
+#[cfg(unix)]
+impl Source for SuspendSignal {
+    fn run(mut self, tx: mpsc::Sender<Event>) {
+        for _ in self.0.forever() {
+            if tx.send(Event::Suspend).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// 決まった順にイベントを送る（テスト用）
+#[cfg(test)]
+pub struct Script(pub Vec<Event>);
+
+#[cfg(test)]
+impl Source for Script {
+    fn run(self, tx: mpsc::Sender<Event>) {
+        for event in self.0 {
+            if tx.send(event).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// イベントの受け口。発生源ごとにスレッドを立て、届いた順に 1 つずつ取り出す。
+pub struct Events {
+    rx: mpsc::Receiver<Event>,
+    tx: mpsc::Sender<Event>,
+}
+
+impl Default for Events {
+    fn default() -> Events {
+        Events::new()
+    }
+}
+
 impl Events {
+    /// 発生源のない受け口。`spawn` で足していく。
     pub fn new() -> Events {
-        Events::with_config(Config::default())
+        let (tx, rx) = mpsc::channel();
+        Events { rx, tx }
     }
 
-    pub fn with_config(config: Config) -> Events {
-        let (tx, rx) = mpsc::channel();
-        let input_handle = {
-            let tx = tx.clone();
-            thread::spawn(move || {
-                let stdin = io::stdin();
-                for evt in stdin.keys() {
-                    if let Ok(key) = evt {
-                        if let Err(err) = tx.send(Event::Input(key)) {
-                            eprintln!("{}", err);
-                            return;
-                        }
-                    }
-                }
-            })
-        };
-        let tick_handle = {
-            thread::spawn(move || loop {
-                if let Err(err) = tx.send(Event::Tick) {
-                    eprintln!("{}", err);
-                    break;
-                }
-                thread::sleep(config.tick_rate);
-            })
-        };
-        Events {
-            rx,
-            input_handle,
-            tick_handle,
-        }
+    pub fn spawn<S: Source>(&self, source: S) {
+        let tx = self.tx.clone();
+        thread::spawn(move || source.run(tx));
+    }
+
+    /// 別スレッドの処理に渡す送り口
+    pub fn sender(&self) -> mpsc::Sender<Event> {
+        self.tx.clone()
     }
 
-    pub fn next(&self) -> Result<Event<Key>, mpsc::RecvError> {
+    /// 次のイベントを待つ
+    pub fn next(&self) -> Result<Event, mpsc::RecvError> {
         self.rx.recv()
     }
+
+    /// 届いているイベントがあれば取り出す
+    #[cfg(test)]
+    pub fn try_next(&self) -> Option<Event> {
+        self.rx.try_recv().ok()
+    }
+
+    #[cfg(test)]
+    pub fn next_timeout(&self, timeout: Duration) -> Option<Event> {
+        self.rx.recv_timeout(timeout).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::{KeyCode, KeyModifiers};
+
+    fn key(c: char) -> Event {
+        Event::Key(KeyEvent {
+            code: KeyCode::Char(c),
+            modifiers: KeyModifiers::NONE,
+        })
+    }
+
+    #[test]
+    fn script_events_arrive_in_order() {
+        let events = Events::new();
+        events.spawn(Script(vec![key('a'), Event::Resize, key('b')]));
+        let mut received = Vec::new();
+        for _ in 0..3 {
+            received.push(events.next_timeout(Duration::from_secs(1)).expect("event"));
+        }
+        match received.as_slice() {
+            [Event::Key(a), Event::Resize, Event::Key(b)] => {
+                assert_eq!((a.code, b.code), (KeyCode::Char('a'), KeyCode::Char('b')));
+            }
+            other => panic!("unexpected events {:?}", other),
+        }
+    }
+
+    #[test]
+    fn ticker_and_jobs_share_the_channel() {
+        let events = Events::new();
+        events.sender().send(Event::Job(Job::Progress)).unwrap();
+        events.spawn(Ticker(Duration::from_millis(1)));
+        assert!(matches!(events.try_next(), Some(Event::Job(Job::Progress))));
+        assert!(matches!(events.next_timeout(Duration::from_secs(1)), Some(Event::Tick)));
+    }
 }
@@ -1,67 +1,15 @@
-#[cfg(feature = "termion")]
 pub mod event;
 
-use rand::distributions::{Distribution, Uniform};
-use rand::rngs::ThreadRng;
+use std::ops::Range;
 use tui::widgets::ListState;
 
-#[derive(Clone)]
-pub struct RandomSignal {
-    distribution: Uniform<u64>,
-    rng: ThreadRng,
-}
-
-impl RandomSignal {
-    pub fn new(lower: u64, upper: u64) -> RandomSignal {
-        RandomSignal {
-            distribution: Uniform::new(lower, upper),
-            rng: rand::thread_rng(),
-        }
-    }
-}
-
-impl Iterator for RandomSignal {
-    type Item = u64;
-    fn next(&mut self) -> Option<u64> {
-        Some(self.distribution.sample(&mut self.rng))
-    }
-}
-
-#[derive(Clone)]
-pub struct SinSignal {
-    x: f64,
-    interval: f64,
-    period: f64,
-    scale: f64,
-}
-
-impl SinSignal {
-    pub fn new(interval: f64, period: f64, scale: f64) -> SinSignal {
-        SinSignal {
-            x: 0.0,
-            interval,
-            period,
-            scale,
-        }
-    }
-}
-
-impl Iterator for SinSignal {
-    type Item = (f64, f64);
-    fn next(&mut self) -> Option<Self::Item> {
-        let point = (self.x, (self.x * 1.0 / self.period).sin() * self.scale);
-        self.x += self.interval;
-        Some(point)
-    }
-}
-
-pub struct TabsState<'a> {
-    pub titles: Vec<&'a str>,
+pub struct TabsState<T> {
+    pub titles: Vec<T>,
     pub index: usize,
 }
 
-impl<'a> TabsState<'a> {
-    pub fn new(titles: Vec<&'a str>) -> TabsState {
+impl<T> TabsState<T> {
+    pub fn new(titles: Vec<T>) -> TabsState<T> {
         TabsState { titles, index: 0 }
     }
     pub fn next(&mut self) {
@@ -80,6 +28,10 @@ impl<'a> TabsState<'a> {
 pub struct StatefulList<T> {
     pub state: ListState,
     pub items: Vec<T>,
+    /// 画面に表示している先頭の行
+    pub offset: usize,
+    /// 表示用に整形した行のキャッシュ。一覧を差し替えると新しい `StatefulList` ごと作り直される。
+    rows: Vec<Option<String>>,
 }
 
 impl<T> StatefulList<T> {
@@ -87,6 +39,8 @@ impl<T> StatefulList<T> {
         StatefulList {
             state: ListState::default(),
             items: Vec::new(),
+            offset: 0,
+            rows: Vec::new(),
         }
     }
 
@@ -94,10 +48,15 @@ impl<T> StatefulList<T> {
         StatefulList {
             state: ListState::default(),
             items,
+            offset: 0,
+            rows: Vec::new(),
         }
     }
 
     pub fn next(&mut self) {
+        if self.items.is_empty() {
+            return;
+        }
         let i = match self.state.selected() {
             Some(i) => {
                 if i >= self.items.len() - 1 {
@@ -112,6 +71,9 @@ impl<T> StatefulList<T> {
     }
 
     pub fn previous(&mut self) {
+        if self.items.is_empty() {
+            return;
+        }
         let i = match self.state.selected() {
             Some(i) => {
                 if i == 0 {
@@ -125,7 +87,61 @@ impl<T> StatefulList<T> {
         self.state.select(Some(i));
     }
 
-    pub fn unselect(&mut self) {
-        self.state.select(None);
+    pub fn first(&mut self) {
+        if !self.items.is_empty() {
+            self.state.select(Some(0));
+        }
+    }
+
+    pub fn last(&mut self) {
+        if !self.items.is_empty() {
+            self.state.select(Some(self.items.len() - 1));
+        }
+    }
+
+    /// 端で止まるように `delta` 行動かす（ホイール用）
+    pub fn move_by(&mut self, delta: isize) {
+        if self.items.is_empty() {
+            return;
+        }
+        let current = self.state.selected().unwrap_or(0) as isize;
+        let last = self.items.len() as isize - 1;
+        self.state.select(Some((current + delta).clamp(0, last) as usize));
+    }
+
+    /// 選択行が `height` 行の中に収まるよう `offset` を動かし、表示する範囲を返す
+    pub fn window(&mut self, height: usize) -> Range<usize> {
+        if let Some(selected) = self.state.selected() {
+            if selected < self.offset {
+                self.offset = selected;
+            } else if height > 0 && selected >= self.offset + height {
+                self.offset = selected + 1 - height;
+            }
+        }
+        self.offset = self.offset.min(self.items.len().saturating_sub(height));
+        self.offset..(self.offset + height).min(self.items.len())
+    }
+
+    /// `index` 行目をまだ整形していなければ `format` で作ってキャッシュする
+    pub fn row<F>(&mut self, index: usize, format: F)
+    where
+        F: FnOnce(&T) -> String,
+    {
+        if self.rows.len() < self.items.len() {
+            self.rows.resize(self.items.len(), None);
+        }
+        if self.rows[index].is_none() {
+            self.rows[index] = Some(format(&self.items[index]));
+        }
+    }
+
+    /// `row` で整形済みの行。まだなければ空文字。
+    pub fn cached(&self, index: usize) -> &str {
+        self.rows.get(index).and_then(|row| row.as_deref()).unwrap_or("")
+    }
+
+    /// 要素をその場で書き換えたときに、整形済みの行を捨てる
+    pub fn invalidate(&mut self) {
+        self.rows.clear();
     }
 }